@@ -0,0 +1,175 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Expr, Field, Fields, LitStr, Path, Token, Type};
+
+// how a field is read from its table
+struct FieldOptions {
+    key: String,
+    default: Option<Expr>,
+}
+
+impl FieldOptions {
+    // #[toml(rename = "key", default)] or #[toml(default = "path::to::fn")]
+    fn from_field(field: &Field) -> syn::Result<Self> {
+        let mut options = Self {
+            // checked that the struct has named fields
+            key: field.ident.as_ref().unwrap().to_string(),
+            default: None,
+        };
+
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("toml"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    options.key = meta.value()?.parse::<LitStr>()?.value();
+                } else if meta.path.is_ident("default") {
+                    options.default = Some(if meta.input.peek(Token![=]) {
+                        let path = meta.value()?.parse::<LitStr>()?.parse::<Path>()?;
+                        syn::parse_quote!(#path())
+                    } else {
+                        syn::parse_quote!(::std::default::Default::default())
+                    });
+                } else {
+                    return Err(meta.error("expected `rename` or `default`"));
+                }
+
+                Ok(())
+            })?;
+        }
+
+        Ok(options)
+    }
+}
+
+// `impl TryFrom<&TomlValue> for Struct`, every field is converted with its
+// own `TryFrom<&TomlValue>` so structs deriving `FromToml` can be nested
+pub fn derive(input: DeriveInput) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "FromToml can't be derived for generic types",
+        ));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    ident,
+                    "FromToml can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                ident,
+                "FromToml can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut conversions = Vec::with_capacity(fields.len());
+
+    for field in fields {
+        let name = &field.ident;
+        let options = FieldOptions::from_field(field)?;
+        let key = &options.key;
+
+        let convert = quote! {
+            ::std::convert::TryFrom::try_from(value)
+                .map_err(|err: ::rtoml::error::FromTomlError| err.at(#key))?
+        };
+
+        // optional fields are `None` when missing instead of an error
+        let (present, missing) = match (&options.default, is_option(&field.ty)) {
+            (Some(default), true) => (
+                quote!(::std::option::Option::Some(#convert)),
+                quote!(#default),
+            ),
+            (None, true) => (
+                quote!(::std::option::Option::Some(#convert)),
+                quote!(::std::option::Option::None),
+            ),
+            (Some(default), false) => (convert, quote!(#default)),
+            (None, false) => (
+                convert,
+                quote!(return ::std::result::Result::Err(
+                    ::rtoml::error::FromTomlError::missing().at(#key)
+                )),
+            ),
+        };
+
+        conversions.push(quote! {
+            #name: match table.get(&::rtoml::TomlKey::from(#key)) {
+                ::std::option::Option::Some(value) => #present,
+                ::std::option::Option::None => #missing,
+            }
+        });
+    }
+
+    Ok(quote! {
+        impl ::std::convert::TryFrom<&::rtoml::TomlValue<'_>> for #ident {
+            type Error = ::rtoml::error::FromTomlError;
+
+            fn try_from(value: &::rtoml::TomlValue<'_>) -> ::std::result::Result<Self, Self::Error> {
+                let table = value
+                    .as_table()
+                    .ok_or_else(|| ::rtoml::error::FromTomlError::mismatch("table", value))?;
+
+                ::std::result::Result::Ok(Self {
+                    #(#conversions),*
+                })
+            }
+        }
+    })
+}
+
+// `Option<T>` is recognised by name, a type alias for it is treated like any
+// other type and has to be present
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsupported_items() {
+        let input = syn::parse_quote!(
+            struct Wrapper(u8);
+        );
+        assert!(derive(input).is_err());
+
+        let input = syn::parse_quote!(
+            struct Generic<T> {
+                value: T,
+            }
+        );
+        assert!(derive(input).is_err());
+
+        let input = syn::parse_quote!(
+            struct Unknown {
+                #[toml(skip)]
+                value: u8,
+            }
+        );
+        assert_eq!(
+            derive(input).unwrap_err().to_string(),
+            "expected `rename` or `default`"
+        );
+    }
+}
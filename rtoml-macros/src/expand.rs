@@ -0,0 +1,289 @@
+use crate::input::{MacroInput, Shape};
+
+use rtoml::error::LineIndex;
+use rtoml::prelude::*;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use proc_macro2::{Literal, Span, TokenStream};
+use quote::quote;
+use syn::{Error, Ident};
+
+pub enum Source {
+    File,
+    Inline,
+}
+
+pub fn expand(input: MacroInput, source: Source) -> syn::Result<TokenStream> {
+    let span = input.literal.span();
+
+    let (document, origin, tracked) = match source {
+        Source::File => {
+            let root = env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+            let path = PathBuf::from(root).join(input.literal.value());
+            let document = fs::read_to_string(&path).map_err(|err| {
+                Error::new(span, format!("couldn't read {}: {}", path.display(), err))
+            })?;
+            let path = path.display().to_string();
+
+            (document, path.clone(), Some(path))
+        }
+        Source::Inline => (input.literal.value(), String::from("toml!"), None),
+    };
+
+    let value = TomlValue::try_from(document.as_str())
+        .map_err(|err| Error::new(span, describe(&err, &document, &origin)))?;
+
+    let expanded = match &input.shape {
+        Some(shape) => typed(&value, Some(shape), "", span)?,
+        None => untyped(&value),
+    };
+
+    // including the file makes cargo rebuild the crate when it changes
+    Ok(match tracked {
+        Some(path) => quote!({
+            const _: &str = include_str!(#path);
+            #expanded
+        }),
+        None => expanded,
+    })
+}
+
+// file:line:column: message, lines and columns start at 1 and columns are
+// counted in characters like the ones rustc reports
+fn describe(err: &TomlError, document: &str, origin: &str) -> String {
+    match err.span() {
+        Some(span) => {
+            let index = LineIndex::new(document);
+            let position = index.position(span.start);
+            let line = index.line(position.line).unwrap_or_default();
+            let column = line[..position.column.min(line.len())].chars().count();
+
+            format!(
+                "{}:{}:{}: {}",
+                origin,
+                position.line + 1,
+                column + 1,
+                err.message()
+            )
+        }
+        None => format!("{}: {}", origin, err.message()),
+    }
+}
+
+fn untyped(value: &TomlValue) -> TokenStream {
+    match value {
+        TomlValue::Int(x) => quote!(::rtoml::TomlValue::Int(#x)),
+        TomlValue::Float(x) => {
+            // the bits keep nan and inf intact
+            let bits = x.to_bits();
+            quote!(::rtoml::TomlValue::Float(f64::from_bits(#bits)))
+        }
+        TomlValue::String(x) => quote!(::rtoml::TomlValue::String(::std::string::String::from(#x))),
+        TomlValue::Boolean(x) => quote!(::rtoml::TomlValue::Boolean(#x)),
+        TomlValue::DateTime(x) => {
+            let datetime = datetime(x);
+            quote!(::rtoml::TomlValue::DateTime(#datetime))
+        }
+        TomlValue::Array(x) => {
+            let values = x.iter().map(untyped);
+            quote!(::rtoml::TomlValue::Array(::std::vec![#(#values),*]))
+        }
+        TomlValue::Table(x) => {
+            let keys = x.keys().map(|key| key.to_string());
+            let values = x.values().map(untyped);
+            quote!({
+                let mut table = ::rtoml::Table::default();
+                #(table.insert(::rtoml::TomlKey::from(#keys), #values);)*
+                ::rtoml::TomlValue::Table(table)
+            })
+        }
+    }
+}
+
+// values are written so that their type is inferred from the fields of the
+// struct they're assigned to, strings go through `Into` to fill `String`
+// and `&'static str` fields alike. Fields given as `Option<..>` are wrapped
+// in `Some` and set to `None` when the document doesn't have them
+fn typed(
+    value: &TomlValue,
+    shape: Option<&Shape>,
+    key: &str,
+    span: Span,
+) -> syn::Result<TokenStream> {
+    Ok(match value {
+        TomlValue::Int(x) => {
+            let literal = Literal::i64_unsuffixed(*x);
+            quote!(#literal)
+        }
+        TomlValue::Float(x) if x.is_nan() => quote!(0.0 / 0.0),
+        TomlValue::Float(x) if x.is_infinite() && *x > 0.0 => quote!(1.0 / 0.0),
+        TomlValue::Float(x) if x.is_infinite() => quote!(-1.0 / 0.0),
+        TomlValue::Float(x) => {
+            let literal = Literal::f64_unsuffixed(*x);
+            quote!(#literal)
+        }
+        TomlValue::String(x) => quote!(::std::convert::Into::into(#x)),
+        TomlValue::Boolean(x) => quote!(#x),
+        TomlValue::DateTime(x) => datetime(x),
+        TomlValue::Array(x) => {
+            let values = x
+                .iter()
+                .map(|value| typed(value, shape, key, span))
+                .collect::<syn::Result<Vec<_>>>()?;
+            quote!(::std::vec![#(#values),*])
+        }
+        TomlValue::Table(x) => {
+            let (path, shape) = shape
+                .and_then(|shape| Some((shape.path.as_ref()?, shape)))
+                .ok_or_else(|| {
+                    Error::new(span, format!("no type given for the table `{}`", key))
+                })?;
+            let mut fields = Vec::with_capacity(x.len());
+            let mut present = Vec::with_capacity(x.len());
+
+            for (key, value) in x {
+                let key = key.to_string();
+                let ident = field(&key, span)?;
+                let field_shape = shape.field(&ident.to_string());
+                let value = typed(value, field_shape, &key, span)?;
+
+                if field_shape.is_some_and(|shape| shape.optional) {
+                    fields.push(quote!(#ident: ::std::option::Option::Some(#value)));
+                } else {
+                    fields.push(quote!(#ident: #value));
+                }
+                present.push(ident.to_string());
+            }
+
+            for (ident, field_shape) in &shape.fields {
+                if field_shape.optional && !present.contains(&ident.to_string()) {
+                    let mut ident = ident.clone();
+                    ident.set_span(span);
+                    fields.push(quote!(#ident: ::std::option::Option::None));
+                }
+            }
+
+            quote!(#path { #(#fields),* })
+        }
+    })
+}
+
+// `kebab-case` keys are assigned to `snake_case` fields, a key that's a
+// keyword to a raw identifier like `r#type`
+fn field(key: &str, span: Span) -> syn::Result<Ident> {
+    let name = key.replace('-', "_");
+    let mut ident = syn::parse_str::<Ident>(&name)
+        .or_else(|_| syn::parse_str::<Ident>(&format!("r#{}", name)))
+        .map_err(|_| Error::new(span, format!("the key `{}` isn't a valid field name", key)))?;
+    ident.set_span(span);
+
+    Ok(ident)
+}
+
+fn datetime(datetime: &DateTime) -> TokenStream {
+    let string = datetime.to_string();
+    quote!(<::rtoml::DateTime as ::std::str::FromStr>::from_str(#string).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inline(document: &str, shape: Option<&str>) -> syn::Result<String> {
+        let input = MacroInput {
+            literal: syn::LitStr::new(document, Span::call_site()),
+            shape: shape.map(|shape| syn::parse_str(shape).unwrap()),
+        };
+
+        expand(input, Source::Inline).map(|tokens| tokens.to_string())
+    }
+
+    #[test]
+    fn error_location() {
+        let err = inline("a = 1\nb = 2\n[c d]\n", None).unwrap_err();
+        assert!(err.to_string().starts_with("toml!:3:2: "));
+
+        let err = TomlValue::try_from("[a b]").unwrap_err();
+        assert_eq!(
+            describe(&err, "[a b]", "config.toml"),
+            "config.toml:1:2: Unexpected `a b`, expected `]`"
+        );
+
+        // errors of the lexer only have a span
+        let document = "a = 1\nb = \"\u{1}\"";
+        let err = TomlValue::try_from(document).unwrap_err();
+        assert_eq!(
+            describe(&err, document, "config.toml"),
+            "config.toml:2:6: Invalid control character while lexing"
+        );
+
+        // columns are counted in characters
+        let document = "a = \"é\" b";
+        let err = TomlValue::try_from(document).unwrap_err();
+        assert_eq!(
+            describe(&err, document, "toml!"),
+            "toml!:1:8: Unexpected `b`, expected a new line"
+        );
+    }
+
+    #[test]
+    fn typed_tables() {
+        let expanded = inline(
+            "[server]\nhost-name = \"a\"",
+            Some("Config { server: Server }"),
+        );
+        assert_eq!(
+            expanded.unwrap(),
+            quote!(Config {
+                server: Server {
+                    host_name: ::std::convert::Into::into("a")
+                }
+            })
+            .to_string()
+        );
+
+        let err = inline("[server]\nport = 1", Some("Config { server: _ }")).unwrap_err();
+        assert_eq!(err.to_string(), "no type given for the table `server`");
+
+        let err = inline("[server]\nport = 1", Some("Config")).unwrap_err();
+        assert_eq!(err.to_string(), "no type given for the table `server`");
+
+        let err = inline("\"a b\" = 1", Some("Config")).unwrap_err();
+        assert_eq!(err.to_string(), "the key `a b` isn't a valid field name");
+
+        // keywords are raw identifiers, except the ones that can't be
+        let expanded = inline("type = 1\nmatch = true", Some("Config"));
+        assert_eq!(
+            expanded.unwrap(),
+            quote!(Config {
+                r#type: 1,
+                r#match: true
+            })
+            .to_string()
+        );
+
+        let err = inline("self = 1", Some("Config")).unwrap_err();
+        assert_eq!(err.to_string(), "the key `self` isn't a valid field name");
+    }
+
+    #[test]
+    fn optional_fields() {
+        let expanded = inline(
+            "a = 1\n[server]\nport = 1",
+            Some("Config { a: Option<_>, b: Option<_>, server: Option<Server>, client: Option<Client> }"),
+        );
+        assert_eq!(
+            expanded.unwrap(),
+            quote!(Config {
+                a: ::std::option::Option::Some(1),
+                server: ::std::option::Option::Some(Server { port: 1 }),
+                b: ::std::option::Option::None,
+                client: ::std::option::Option::None
+            })
+            .to_string()
+        );
+    }
+}
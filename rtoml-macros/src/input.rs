@@ -0,0 +1,96 @@
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{braced, Ident, LitStr, Path, Token};
+
+// "path/or/document" ( as Type { field: Type { .. }, field: Option<_>, .. } )?
+pub struct MacroInput {
+    pub literal: LitStr,
+    pub shape: Option<Shape>,
+}
+
+// the types used for a table and the tables nested in it, tables without a
+// type can't be turned into a struct literal
+pub struct Shape {
+    // `None` for `_`, the type of a value that isn't a table is inferred
+    pub path: Option<Path>,
+    pub fields: Vec<(Ident, Shape)>,
+    // given as `Option<..>`
+    pub optional: bool,
+}
+
+impl Shape {
+    pub fn field(&self, name: &str) -> Option<&Shape> {
+        self.fields
+            .iter()
+            .find(|(ident, _)| ident == name)
+            .map(|(_, shape)| shape)
+    }
+}
+
+impl Parse for MacroInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let literal = input.parse()?;
+        let mut shape = None;
+
+        if input.parse::<Option<Token![as]>>()?.is_some() {
+            shape = Some(input.parse()?);
+        }
+
+        Ok(Self { literal, shape })
+    }
+}
+
+impl Parse for Shape {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.parse::<Option<Token![_]>>()?.is_some() {
+            return Ok(Self {
+                path: None,
+                fields: Vec::new(),
+                optional: false,
+            });
+        }
+
+        let path = Path::parse_mod_style(input)?;
+        let mut fields = Vec::new();
+
+        if path.is_ident("Option") && input.peek(Token![<]) {
+            input.parse::<Token![<]>()?;
+            let mut shape: Shape = input.parse()?;
+            input.parse::<Token![>]>()?;
+            shape.optional = true;
+
+            return Ok(shape);
+        }
+
+        if input.peek(syn::token::Brace) {
+            let content;
+            braced!(content in input);
+
+            let parsed = Punctuated::<ShapeField, Token![,]>::parse_terminated(&content)?;
+            fields.extend(parsed.into_iter().map(|field| (field.ident, field.shape)));
+        }
+
+        Ok(Self {
+            path: Some(path),
+            fields,
+            optional: false,
+        })
+    }
+}
+
+struct ShapeField {
+    ident: Ident,
+    shape: Shape,
+}
+
+impl Parse for ShapeField {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident = input.parse()?;
+        input.parse::<Token![:]>()?;
+
+        Ok(Self {
+            ident,
+            shape: input.parse()?,
+        })
+    }
+}
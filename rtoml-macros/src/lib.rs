@@ -0,0 +1,48 @@
+//! Compile time toml embedding for `rtoml`
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+mod derive;
+mod expand;
+mod input;
+
+use crate::expand::{expand, Source};
+use crate::input::MacroInput;
+
+// `include_toml!("config.toml")` parses a file, relative to the root of the
+// calling crate, while compiling and expands to the `TomlValue<'static>` it
+// describes. With a target type, `include_toml!("config.toml" as Config)`,
+// it expands to a struct literal instead.
+//
+// The document is checked while compiling but the value is built when the
+// expression runs, `TomlValue` owns its strings and tables so it can't be a
+// `const`, nor can struct literals with `String` or `Vec` fields
+#[proc_macro]
+pub fn include_toml(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as MacroInput);
+
+    expand(input, Source::File)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+// same as `include_toml!` but the document is given as a string literal
+#[proc_macro]
+pub fn toml(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as MacroInput);
+
+    expand(input, Source::Inline)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+// `#[derive(FromToml)]` implements `TryFrom<&TomlValue>` for a struct, see
+// the README for the supported `#[toml(..)]` attributes
+#[proc_macro_derive(FromToml, attributes(toml))]
+pub fn from_toml(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    derive::derive(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
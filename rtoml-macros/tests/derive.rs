@@ -0,0 +1,120 @@
+use rtoml::prelude::*;
+use rtoml_macros::FromToml;
+
+#[derive(Debug, PartialEq, FromToml)]
+struct Config {
+    title: String,
+    #[toml(default)]
+    debug: bool,
+    owner: Option<Owner>,
+    server: Server,
+    #[toml(rename = "route")]
+    routes: Vec<Route>,
+}
+
+#[derive(Debug, PartialEq, FromToml)]
+struct Owner {
+    name: String,
+}
+
+#[derive(Debug, PartialEq, FromToml)]
+struct Server {
+    #[toml(rename = "host-name")]
+    host_name: String,
+    port: u16,
+    #[toml(default = "default_workers")]
+    workers: usize,
+    released: Option<DateTime>,
+}
+
+#[derive(Debug, PartialEq, FromToml)]
+struct Route {
+    path: String,
+    methods: Vec<String>,
+}
+
+fn default_workers() -> usize {
+    4
+}
+
+fn parse<'a, T>(toml: &'a str) -> Result<T, FromTomlError>
+where
+    T: for<'v> TryFrom<&'v TomlValue<'a>, Error = FromTomlError>,
+{
+    T::try_from(&TomlValue::try_from(toml).unwrap())
+}
+
+#[test]
+fn derive_struct() {
+    let toml = r#"
+title = "example"
+
+[server]
+host-name = "localhost"
+port = 8080
+
+[[route]]
+path = "/"
+methods = ["GET"]
+
+[[route]]
+path = "/api"
+methods = ["GET", "POST"]
+"#;
+
+    assert_eq!(
+        parse::<Config>(toml).unwrap(),
+        Config {
+            title: String::from("example"),
+            debug: false,
+            owner: None,
+            server: Server {
+                host_name: String::from("localhost"),
+                port: 8080,
+                workers: 4,
+                released: None,
+            },
+            routes: vec![
+                Route {
+                    path: String::from("/"),
+                    methods: vec![String::from("GET")],
+                },
+                Route {
+                    path: String::from("/api"),
+                    methods: vec![String::from("GET"), String::from("POST")],
+                },
+            ],
+        }
+    );
+}
+
+#[test]
+fn error_paths() {
+    let err =
+        parse::<Config>("title = \"a\"\nroute = []\n[server]\nhost-name = \"a\"\nport = \"80\"");
+    assert_eq!(
+        err.unwrap_err().to_string(),
+        "server.port: expected integer, found string"
+    );
+
+    let err = parse::<Config>("title = \"a\"\n[server]\nport = 80");
+    assert_eq!(
+        err.unwrap_err().to_string(),
+        "server.host-name: missing field"
+    );
+
+    let err = parse::<Config>(
+        "title = \"a\"\n[server]\nhost-name = \"a\"\nport = 80\n[[route]]\npath = \"/\"\nmethods = [1]",
+    );
+    assert_eq!(
+        err.unwrap_err().to_string(),
+        "route[0].methods[0]: expected string, found integer"
+    );
+
+    let err = parse::<Owner>("name = \"a\"").map(|_| ());
+    assert!(err.is_ok());
+    assert_eq!(
+        Owner::try_from(&TomlValue::Int(1)).unwrap_err().to_string(),
+        "expected table, found integer"
+    );
+}
@@ -0,0 +1,115 @@
+use rtoml::prelude::*;
+use rtoml_macros::{include_toml, toml};
+
+#[derive(Debug, PartialEq)]
+struct Config {
+    title: &'static str,
+    released: DateTime,
+    server: Server,
+    routes: Vec<Route>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Server {
+    host_name: String,
+    ports: Vec<u16>,
+    ratio: f32,
+}
+
+#[derive(Debug, PartialEq)]
+struct Route {
+    path: String,
+    cached: bool,
+}
+
+// a missing field or one that isn't an `Option` would fail to compile
+#[derive(Debug, PartialEq)]
+struct Optional {
+    title: Option<&'static str>,
+    released: DateTime,
+    server: Option<Server>,
+    client: Option<Server>,
+    timeout: Option<u32>,
+    routes: Vec<Route>,
+}
+
+const CONFIG: &str = include_str!("config.toml");
+
+#[test]
+fn include_value() {
+    let value: TomlValue<'static> = include_toml!("tests/config.toml");
+    assert_eq!(value, TomlValue::try_from(CONFIG).unwrap());
+}
+
+#[test]
+fn include_struct() {
+    let config = include_toml!("tests/config.toml" as Config {
+        server: Server,
+        routes: Route,
+    });
+
+    assert_eq!(config.title, "embedded");
+    assert_eq!(config.released.to_string(), "1979-05-27T07:32:00Z");
+    assert_eq!(
+        config.server,
+        Server {
+            host_name: String::from("127.0.0.1"),
+            ports: vec![8000, 8001],
+            ratio: -0.5,
+        }
+    );
+    assert_eq!(config.routes.len(), 2);
+    assert_eq!(config.routes[1].path, "/api");
+    assert!(!config.routes[1].cached);
+}
+
+#[test]
+fn include_optional() {
+    let config = include_toml!("tests/config.toml" as Optional {
+        title: Option<_>,
+        server: Option<Server>,
+        client: Option<Server>,
+        timeout: Option<_>,
+        routes: Route,
+    });
+
+    assert_eq!(config.title, Some("embedded"));
+    assert_eq!(config.server.unwrap().ports, vec![8000, 8001]);
+    assert_eq!(config.client, None);
+    assert_eq!(config.timeout, None);
+    assert_eq!(config.routes.len(), 2);
+}
+
+#[test]
+fn inline_document() {
+    let value = toml!("a = { b = [nan, inf, -inf, 1.5] }\nc = 07:32:00");
+    let table = value.as_table().unwrap();
+    let b = table[&TomlKey::from("a")].as_table().unwrap()[&TomlKey::from("b")]
+        .as_array()
+        .unwrap();
+
+    assert!(b[0].as_floating().unwrap().is_nan());
+    assert_eq!(b[1], TomlValue::Float(f64::INFINITY));
+    assert_eq!(b[2], TomlValue::Float(f64::NEG_INFINITY));
+    assert_eq!(b[3], TomlValue::Float(1.5));
+    assert_eq!(table[&TomlKey::from("c")].to_string(), "07:32:00");
+}
+
+#[derive(Debug, PartialEq)]
+struct Keywords {
+    r#type: &'static str,
+    r#match: bool,
+}
+
+#[test]
+fn keyword_keys() {
+    let keywords = toml!("type = \"a\"\nmatch = true" as Keywords);
+
+    assert_eq!(
+        keywords,
+        Keywords {
+            r#type: "a",
+            r#match: true,
+        }
+    );
+}
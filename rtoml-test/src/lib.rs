@@ -0,0 +1,264 @@
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+
+use rtoml::prelude::*;
+use serde_json::{Map, Value};
+
+// the json format used by https://github.com/BurntSushi/toml-test, scalars
+// are written as `{"type": "integer", "value": "1"}` while arrays and tables
+// map to json arrays and objects
+pub fn to_tagged_json(value: &TomlValue) -> Value {
+    match value {
+        TomlValue::Int(x) => tagged("integer", x.to_string()),
+        TomlValue::Float(x) => tagged("float", format_float(*x)),
+        TomlValue::String(x) => tagged("string", x.clone()),
+        TomlValue::Boolean(x) => tagged("bool", x.to_string()),
+        TomlValue::DateTime(x) => tagged(datetime_type(x), x.to_string()),
+        TomlValue::Array(x) => x.iter().map(to_tagged_json).collect(),
+        TomlValue::Table(x) => x
+            .iter()
+            .map(|(key, value)| (key.to_string(), to_tagged_json(value)))
+            .collect(),
+    }
+}
+
+fn tagged(ty: &str, value: String) -> Value {
+    let mut map = Map::new();
+    map.insert(String::from("type"), Value::from(ty));
+    map.insert(String::from("value"), Value::from(value));
+    Value::Object(map)
+}
+
+fn format_float(float: f64) -> String {
+    if float.is_nan() {
+        String::from("nan")
+    } else if float.is_infinite() {
+        String::from(if float > 0.0 { "inf" } else { "-inf" })
+    } else {
+        format!("{:?}", float)
+    }
+}
+
+fn datetime_type(datetime: &DateTime) -> &'static str {
+    match datetime {
+        DateTime::DateTime(x) if x.offset.is_some() => "datetime",
+        DateTime::DateTime(_) => "datetime-local",
+        DateTime::Date(_) => "date-local",
+        DateTime::Time(_) => "time-local",
+    }
+}
+
+// compare two tagged documents, floats are compared by value since `1e2` and
+// `100.0` are the same value. Datetimes are compared as strings, the corpus
+// has them in the normalized RFC 3339 form rtoml is expected to write
+pub fn tagged_eq(actual: &Value, expected: &Value) -> bool {
+    match (scalar(actual), scalar(expected)) {
+        (Some((ty, actual)), Some((expected_ty, expected))) => {
+            ty == expected_ty
+                && match ty {
+                    "float" => match (parse_float(actual), parse_float(expected)) {
+                        (Some(a), Some(b)) => a == b || (a.is_nan() && b.is_nan()),
+                        _ => false,
+                    },
+                    _ => actual == expected,
+                }
+        }
+        (None, None) => match (actual, expected) {
+            (Value::Array(actual), Value::Array(expected)) => {
+                actual.len() == expected.len()
+                    && actual.iter().zip(expected).all(|(a, b)| tagged_eq(a, b))
+            }
+            (Value::Object(actual), Value::Object(expected)) => {
+                actual.len() == expected.len()
+                    && actual
+                        .iter()
+                        .all(|(key, a)| expected.get(key).is_some_and(|b| tagged_eq(a, b)))
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn scalar(value: &Value) -> Option<(&str, &str)> {
+    let object = value.as_object()?;
+
+    if object.len() != 2 {
+        return None;
+    }
+
+    Some((
+        object.get("type")?.as_str()?,
+        object.get("value")?.as_str()?,
+    ))
+}
+
+fn parse_float(float: &str) -> Option<f64> {
+    match float.trim_start_matches('+') {
+        "nan" | "-nan" => Some(f64::NAN),
+        "inf" => Some(f64::INFINITY),
+        "-inf" => Some(f64::NEG_INFINITY),
+        float => float.parse().ok(),
+    }
+}
+
+// a document of the corpus, `valid/<category>/<name>.toml` has its expected
+// value next to it in a `.json` file while `invalid/` ones must be rejected
+pub struct Case {
+    pub name: String,
+    pub category: String,
+    pub valid: bool,
+    pub path: PathBuf,
+}
+
+pub fn cases(root: &Path) -> Vec<Case> {
+    let mut cases = Vec::new();
+
+    for kind in ["valid", "invalid"] {
+        collect(root, &root.join(kind), kind == "valid", &mut cases);
+    }
+
+    cases.sort_by(|a, b| a.name.cmp(&b.name));
+    cases
+}
+
+fn collect(root: &Path, dir: &Path, valid: bool, cases: &mut Vec<Case>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if path.is_dir() {
+            collect(root, &path, valid, cases);
+        } else if path.extension().is_some_and(|ext| ext == "toml") {
+            let name = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .with_extension("")
+                .to_string_lossy()
+                .replace('\\', "/");
+            let category = name.rsplit_once('/').map_or("", |(dir, _)| dir).to_string();
+
+            cases.push(Case {
+                name,
+                category,
+                valid,
+                path,
+            });
+        }
+    }
+}
+
+impl Case {
+    // `Ok` if rtoml agrees with the corpus, a panic while parsing counts as
+    // a failure
+    pub fn run(&self) -> Result<(), String> {
+        let bytes = fs::read(&self.path).map_err(|err| err.to_string())?;
+
+        let document = match String::from_utf8(bytes) {
+            Ok(document) => document,
+            Err(_) if !self.valid => return Ok(()),
+            Err(err) => return Err(err.to_string()),
+        };
+
+        let parsed = panic::catch_unwind(|| {
+            TomlValue::try_from(document.as_str())
+                .map(|value| to_tagged_json(&value))
+                .map_err(|err| err.to_string())
+        })
+        .map_err(|_| String::from("panicked while parsing"))?;
+
+        match (parsed, self.valid) {
+            (Ok(actual), true) => {
+                let expected = fs::read_to_string(self.path.with_extension("json"))
+                    .map_err(|err| err.to_string())?;
+                let expected: Value =
+                    serde_json::from_str(&expected).map_err(|err| err.to_string())?;
+
+                if !tagged_eq(&actual, &expected) {
+                    return Err(format!("expected {}, found {}", expected, actual));
+                }
+
+                // valid documents are written back out unchanged
+                let written = panic::catch_unwind(|| {
+                    DocumentMut::parse(&document)
+                        .map(|document| document.to_string())
+                        .map_err(|err| err.to_string())
+                });
+
+                match written {
+                    Ok(Ok(written)) if written == document => Ok(()),
+                    Ok(Ok(_)) => Err(String::from("document changed when written back out")),
+                    Ok(Err(err)) => Err(err),
+                    Err(_) => Err(String::from("panicked while parsing a document")),
+                }
+            }
+            (Err(err), true) => Err(err),
+            (Ok(_), false) => Err(String::from("invalid document was accepted")),
+            (Err(_), false) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn tagged() {
+        let value = TomlValue::try_from("a = 1\nb = [inf, 1979-05-27]\n[c]\nd = \"e\"").unwrap();
+
+        assert!(tagged_eq(
+            &to_tagged_json(&value),
+            &json!({
+                "a": {"type": "integer", "value": "1"},
+                "b": [
+                    {"type": "float", "value": "+inf"},
+                    {"type": "date-local", "value": "1979-05-27"}
+                ],
+                "c": {"d": {"type": "string", "value": "e"}}
+            })
+        ));
+        assert!(!tagged_eq(
+            &to_tagged_json(&value),
+            &json!({"a": {"type": "float", "value": "1"}})
+        ));
+    }
+
+    #[test]
+    fn datetimes() {
+        let value = TomlValue::try_from("a = 1979-05-27 07:32:00.5z").unwrap();
+
+        assert!(tagged_eq(
+            &to_tagged_json(&value),
+            &json!({"a": {"type": "datetime", "value": "1979-05-27T07:32:00.5Z"}})
+        ));
+        // the same instant written another way isn't what rtoml should output
+        assert!(!tagged_eq(
+            &to_tagged_json(&value),
+            &json!({"a": {"type": "datetime", "value": "1979-05-27T00:32:00.5-07:00"}})
+        ));
+    }
+
+    #[test]
+    fn key_order() {
+        let value = TomlValue::try_from(
+            "b = 1
+a = 2
+[d]
+[c]",
+        )
+        .unwrap();
+
+        assert_eq!(
+            to_tagged_json(&value).to_string(),
+            r#"{"b":{"type":"integer","value":"1"},"a":{"type":"integer","value":"2"},"d":{},"c":{}}"#
+        );
+    }
+}
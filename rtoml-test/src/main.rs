@@ -1,42 +1,29 @@
 use std::convert::TryFrom;
 use std::error::Error;
-use std::io::{stdin, BufRead, Read};
+use std::io::{stdin, Read};
+use std::process;
 use std::string::String;
 
 use rtoml::prelude::*;
-use serde_json::Value;
+use toml_test::to_tagged_json;
 
+// decoder for toml-test, reads a document from stdin and writes it as tagged
+// json, invalid documents exit with a non zero status
 fn main() -> Result<(), Box<dyn Error>> {
     let mut buffer = String::new();
-    stdin().read_to_string(&mut buffer)?;
 
-    println!(
-        "{}",
-        to_json_value(TomlValue::try_from(buffer.as_str()).expect("Parsing error"))
-    );
-
-    Ok(())
-}
-
-fn to_json_value(value: TomlValue) -> Value {
-    let mut value = match value {
-        TomlValue::Int(x) => Value::from(x),
-        TomlValue::Float(x) => Value::from(x),
-        TomlValue::Boolean(x) => Value::from(x),
-        TomlValue::String(x) => Value::from(x),
-        TomlValue::DateTime(x) => Value::from(x.to_string()),
-        TomlValue::Array(x) => x.into_iter().map(to_json_value).collect(),
-        TomlValue::Table(x) => x
-            .into_iter()
-            .map(|(key, value)| (key.to_string(), to_json_value(value)))
-            .collect(),
-    };
+    if stdin().read_to_string(&mut buffer).is_err() {
+        eprintln!("Invalid UTF8 input");
+        process::exit(1);
+    }
 
-    if let Some(object) = value.as_object_mut() {
-        if let Some(empty) = object.remove("") {
-            value = empty
+    match TomlValue::try_from(buffer.as_str()) {
+        Ok(value) => println!("{}", to_tagged_json(&value)),
+        Err(err) => {
+            eprint!("{}", err.render(&buffer));
+            process::exit(1);
         }
     }
 
-    value
+    Ok(())
 }
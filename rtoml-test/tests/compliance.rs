@@ -0,0 +1,64 @@
+use std::collections::{BTreeMap, HashSet};
+use std::panic;
+use std::path::Path;
+
+use toml_test::cases;
+
+// cases rtoml is known to get wrong, a fix should remove them from the list
+const KNOWN_FAILURES: &str = include_str!("../toml-test/known-failures.txt");
+
+#[test]
+fn toml_test_corpus() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("toml-test");
+    let known: HashSet<&str> = KNOWN_FAILURES
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+
+    // parser panics are reported as failures, keep them from cluttering the
+    // output
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut categories = BTreeMap::new();
+    let mut regressions = Vec::new();
+    let mut fixed = Vec::new();
+
+    for case in cases(&root) {
+        let result = case.run();
+        let (passed, total) = categories.entry(case.category.clone()).or_insert((0, 0));
+        *total += 1;
+
+        match (result, known.contains(case.name.as_str())) {
+            (Ok(()), false) => *passed += 1,
+            (Ok(()), true) => {
+                *passed += 1;
+                fixed.push(case.name);
+            }
+            (Err(err), false) => regressions.push(format!("{}: {}", case.name, err)),
+            (Err(_), true) => (),
+        }
+    }
+
+    let _ = panic::take_hook();
+
+    let (passed, total) = categories
+        .values()
+        .fold((0, 0), |(p, t), (passed, total)| (p + passed, t + total));
+
+    for (category, (passed, total)) in categories.iter() {
+        println!("{:<24} {:>3}/{:<3}", category, passed, total);
+    }
+    println!("{:<24} {:>3}/{:<3}", "total", passed, total);
+
+    assert!(
+        regressions.is_empty(),
+        "failing cases:\n{}",
+        regressions.join("\n")
+    );
+    assert!(
+        fixed.is_empty(),
+        "passing cases listed in known-failures.txt:\n{}",
+        fixed.join("\n")
+    );
+}
@@ -1,10 +1,9 @@
 use std::convert::TryFrom;
-use std::error::Error;
 use std::fs::File;
 use std::io::Read;
 use std::string::String;
 
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, Criterion};
 use rtoml::prelude::*;
 use toml::Value;
 
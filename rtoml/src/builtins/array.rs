@@ -1,24 +1,25 @@
 use crate::builtins::parse_value;
-use crate::error::ErrLocation;
+use crate::error::{ErrLocation, Expected};
 use crate::lexer::Token;
 use crate::parser::r_iter::RIter;
 use crate::parser::r_slice::RSlice;
 use crate::parser::ParsedValue;
 use crate::{TomlError, TomlValue};
 
-pub fn parse_array(slice: RSlice) -> Result<ParsedValue, TomlError> {
+pub fn parse_array<'t, 'a>(slice: RSlice<'t, 'a>) -> Result<ParsedValue<'t, 'a>, TomlError<'a>> {
     let mut iter = RIter::from(slice);
     let mut vec = Vec::new();
 
     while let Some((next, _)) = iter.peek() {
         match next {
             Token::Sbc => {
-                break;
+                iter.next();
+                return Ok(ParsedValue::new(TomlValue::Array(vec), iter));
             }
             Token::Comma => {
                 return Err(TomlError::UnexpectedCharacter(
                     ErrLocation::new(RIter::from(iter.as_slice())),
-                    &[Token::Sbc, Token::Literal("")],
+                    &[Expected::Token(Token::Sbc), Expected::Value],
                 ));
             }
             Token::LineBreak => {
@@ -32,21 +33,32 @@ pub fn parse_array(slice: RSlice) -> Result<ParsedValue, TomlError> {
                 vec.push(parsed.value);
                 iter = parsed.section;
 
+                // whitespace and new lines may come before the comma
+                while iter
+                    .peek()
+                    .is_some_and(|(x, _)| x.is_space() || *x == Token::LineBreak)
+                {
+                    iter.next();
+                }
+
                 if !iter.next_if_eq(Token::Comma) {
                     if !iter.next_if_eq(Token::Sbc) {
                         return Err(TomlError::UnexpectedCharacter(
                             ErrLocation::new(RIter::from(iter.as_slice())),
-                            &[Token::Comma, Token::Sbc],
+                            &[Expected::Token(Token::Comma), Expected::Token(Token::Sbc)],
                         ));
                     } else {
-                        break;
+                        return Ok(ParsedValue::new(TomlValue::Array(vec), iter));
                     }
                 }
             }
         }
     }
 
-    Ok(ParsedValue::new(TomlValue::Array(vec), iter))
+    Err(TomlError::UnexpectedCharacter(
+        ErrLocation::new(iter),
+        &[Expected::Token(Token::Sbc)],
+    ))
 }
 
 #[cfg(test)]
@@ -58,6 +70,16 @@ mod test {
     pub fn simple_nested_int_arr() {
         let str = b"6,[1,2,4]]";
         let lexed = lex(str).unwrap();
-        // println!("{:?}", parse_array(&lexed).unwrap().0);
+        assert_eq!(
+            parse_array(RIter::new(&lexed).as_slice()).unwrap().value,
+            TomlValue::Array(vec![
+                TomlValue::Int(6),
+                TomlValue::Array(vec![
+                    TomlValue::Int(1),
+                    TomlValue::Int(2),
+                    TomlValue::Int(4)
+                ])
+            ])
+        );
     }
 }
@@ -1,56 +1,106 @@
-use crate::builtins::parse_value;
 use crate::lexer::Token;
 use crate::parser::r_iter::RIter;
 use crate::parser::r_slice::RSlice;
-use crate::{TomlError, TomlValue};
+use crate::{Table, TomlError, TomlValue};
 
-use crate::error::ErrLocation;
-use crate::parser::ParsedValue;
-use rustc_hash::FxHashMap;
+use crate::error::{ErrLocation, Expected};
+use crate::parser::{ParsedValue, Tables};
 
-pub fn parse_inline_table(slice: RSlice) -> Result<ParsedValue, TomlError> {
+pub fn parse_inline_table<'t, 'a>(
+    slice: RSlice<'t, 'a>,
+) -> Result<ParsedValue<'t, 'a>, TomlError<'a>> {
     let mut iter = RIter::from(slice);
-    let mut map = FxHashMap::default();
-
-    while let Some((token, _)) = iter.next() {
-        match token {
-            Token::Literal(_) => {
-                if token.is_valid_table_name_or_key() && iter.next_if_eq(Token::Eq) {
-                    let parsed = parse_value(iter.as_slice())?;
-                    map.insert(token.as_key(), parsed.value);
-                    iter = parsed.section;
-                }
-            }
-            Token::Cbc => {
-                break;
-            }
-            Token::Comma => {
-                continue;
+    let mut map = Table::default();
+    let mut tables = Tables::default();
+
+    skip_spaces(&mut iter);
+
+    if iter.next_if_eq(Token::Cbc) {
+        return Ok(ParsedValue::new(TomlValue::Table(map), iter));
+    }
+
+    // key/values separated by commas, without a trailing comma or new lines
+    // https://toml.io/en/v1.0.0#inline-table
+    loop {
+        skip_spaces(&mut iter);
+
+        match iter.peek() {
+            Some((Token::Literal(_), _))
+            | Some((Token::DoubleQuote, _))
+            | Some((Token::SingleQuote, _)) => {
+                ParsedValue::key_value(&mut iter, &mut map, &[], &mut tables)?;
             }
             _ => {
                 return Err(TomlError::UnexpectedCharacter(
                     ErrLocation::new(iter),
-                    &[Token::Cbc, Token::Literal("")],
+                    &[Expected::Key],
                 ));
             }
         }
+
+        skip_spaces(&mut iter);
+
+        if iter.next_if_eq(Token::Cbc) {
+            break;
+        } else if !iter.next_if_eq(Token::Comma) {
+            return Err(TomlError::UnexpectedCharacter(
+                ErrLocation::new(iter),
+                &[Expected::Token(Token::Comma), Expected::Token(Token::Cbc)],
+            ));
+        }
     }
 
     Ok(ParsedValue::new(TomlValue::Table(map), iter))
 }
 
+fn skip_spaces(iter: &mut RIter) {
+    while iter.peek().is_some_and(|(x, _)| x.is_space()) {
+        iter.next();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::lexer::lex;
-    use crate::TomlValue;
+    use crate::{TomlKey, TomlValue};
 
-    use rustc_hash::FxHashMap;
+    #[test]
+    fn separators() {
+        for table in [
+            "}",
+            " }",
+            "a = 1 }",
+            "a = 1, b = \"c\" }",
+            "a = { b = 1 } }",
+        ] {
+            let lexed = &lex(table.as_bytes()).unwrap();
+            assert!(parse_inline_table(RIter::new(lexed).as_slice()).is_ok());
+        }
+
+        for table in [
+            "a = 1, }",
+            "a = 1,, b = 2 }",
+            ", }",
+            "a = 1 b = 2 }",
+            "a = 1\n}",
+            "a = 1",
+        ] {
+            let lexed = &lex(table.as_bytes()).unwrap();
+            assert!(parse_inline_table(RIter::new(lexed).as_slice()).is_err());
+        }
+    }
 
     #[test]
     fn basic_inline_table() {
-        let mut map = FxHashMap::default();
-        map.insert("value", TomlValue::Int(1));
+        let mut map = Table::default();
+        map.insert(TomlKey::from("value"), TomlValue::Int(1));
         let table = &lex(b"value = 1 }").unwrap();
+        assert_eq!(
+            parse_inline_table(RIter::new(table).as_slice())
+                .unwrap()
+                .value,
+            TomlValue::Table(map)
+        );
     }
 }
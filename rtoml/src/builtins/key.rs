@@ -0,0 +1,197 @@
+use crate::builtins::string::parse_string;
+use crate::error::{ErrLocation, Expected};
+use crate::lexer::{is_bare_key, lex, Token};
+use crate::parser::r_iter::RIter;
+use crate::parser::r_slice::RSlice;
+use crate::{TomlError, TomlKey, TomlValue};
+
+// the segments of a key given on its own rather than in a document, like
+// the keys of `LintOptions::known_keys`, `None` when it isn't a valid key
+pub fn parse_dotted_key(key: &str) -> Option<Vec<String>> {
+    let source = format!("{}=", key);
+    let lexed = lex(source.as_bytes()).ok()?;
+    let mut parsed =
+        parse_key(RIter::new(&lexed).as_slice(), &[Expected::Token(Token::Eq)]).ok()?;
+
+    if parsed.section.peek().is_some() {
+        return None;
+    }
+
+    Some(parsed.path.iter().map(ToString::to_string).collect())
+}
+
+pub struct ParsedKey<'t, 'a> {
+    pub path: Vec<TomlKey<'a>>,
+    pub section: RIter<'t, 'a>,
+}
+
+// parse a key made of one or more segments separated by `.`, the
+// terminating token is consumed as well
+// key: https://toml.io/en/v1.0.0#keys
+pub fn parse_key<'t, 'a>(
+    slice: RSlice<'t, 'a>,
+    terminators: &'static [Expected],
+) -> Result<ParsedKey<'t, 'a>, TomlError<'a>> {
+    let mut iter = RIter::from(slice);
+    let mut path = Vec::new();
+    let mut expect_segment = true;
+
+    while let Some((token, _)) = iter.peek() {
+        match token {
+            Token::Literal(literal) => {
+                for (index, segment) in literal.split('.').enumerate() {
+                    if index > 0 {
+                        if expect_segment {
+                            return Err(TomlError::UnexpectedCharacter(
+                                ErrLocation::new(iter),
+                                &[Expected::Key],
+                            ));
+                        }
+                        expect_segment = true;
+                    }
+
+                    let segment = segment.trim();
+
+                    if segment.is_empty() {
+                        continue;
+                    }
+
+                    if !expect_segment || !is_bare_key(segment) {
+                        return Err(TomlError::UnexpectedCharacter(
+                            ErrLocation::new(iter),
+                            terminators,
+                        ));
+                    }
+
+                    path.push(TomlKey::from(segment));
+                    expect_segment = false;
+                }
+
+                iter.next();
+            }
+            Token::DoubleQuote | Token::SingleQuote if expect_segment => {
+                iter.next();
+
+                let (key, section) = parse_quoted_key(iter, *token)?;
+                path.push(key);
+                iter = section;
+                expect_segment = false;
+            }
+            token
+                if !expect_segment
+                    && terminators
+                        .iter()
+                        .any(|t| matches!(t, Expected::Token(t) if t == token)) =>
+            {
+                iter.next();
+                return Ok(ParsedKey {
+                    path,
+                    section: iter,
+                });
+            }
+            _ => break,
+        }
+    }
+
+    Err(TomlError::UnexpectedCharacter(
+        ErrLocation::new(iter),
+        if expect_segment {
+            &[Expected::Key]
+        } else {
+            terminators
+        },
+    ))
+}
+
+// quoted keys follow the same rules as basic and literal strings, but can't
+// span multiple lines
+fn parse_quoted_key<'t, 'a>(
+    mut iter: RIter<'t, 'a>,
+    quote: Token<'a>,
+) -> Result<(TomlKey<'a>, RIter<'t, 'a>), TomlError<'a>> {
+    if iter.next_if_eq(quote) {
+        if iter.next_if_eq(quote) {
+            return Err(TomlError::UnexpectedCharacter(
+                ErrLocation::new(iter),
+                &[Expected::Key],
+            ));
+        }
+
+        return Ok((TomlKey::from(""), iter));
+    }
+
+    // without escape sequences the key can borrow from the input, keys that
+    // are too long are left to `parse_string` to report
+    let mut lookahead = iter.clone();
+    if let (Some((Token::Literal(literal), _)), true) =
+        (lookahead.next(), lookahead.next_if_eq(quote))
+    {
+        if literal.len() <= iter.limits.options.max_string_length {
+            return Ok((TomlKey::from(*literal), lookahead));
+        }
+    }
+
+    let parsed = parse_string(iter.as_slice(), quote)?;
+
+    match parsed.value {
+        TomlValue::String(string) => Ok((TomlKey::from(string), parsed.section)),
+        _ => Err(TomlError::CannotParseValue(ErrLocation::new(
+            parsed.section,
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lex;
+
+    #[test]
+    fn dotted_table_name() {
+        let lexed = &lex(b" a . b-c.d_1 ]").unwrap();
+        let parsed =
+            parse_key(RIter::new(lexed).as_slice(), &[Expected::Token(Token::Sbc)]).unwrap();
+        assert_eq!(
+            parsed.path,
+            vec![
+                TomlKey::from("a"),
+                TomlKey::from("b-c"),
+                TomlKey::from("d_1")
+            ]
+        );
+    }
+
+    #[test]
+    fn quoted_keys() {
+        let lexed = &lex(br#"dog."tater.man" . 'quoted key'."\u0041\t" ]"#).unwrap();
+        let parsed =
+            parse_key(RIter::new(lexed).as_slice(), &[Expected::Token(Token::Sbc)]).unwrap();
+        assert_eq!(
+            parsed.path,
+            vec![
+                TomlKey::from("dog"),
+                TomlKey::from("tater.man"),
+                TomlKey::from("quoted key"),
+                TomlKey::from("A\t")
+            ]
+        );
+
+        let lexed = &lex(br#""" = 1"#).unwrap();
+        let parsed =
+            parse_key(RIter::new(lexed).as_slice(), &[Expected::Token(Token::Eq)]).unwrap();
+        assert_eq!(parsed.path, vec![TomlKey::from("")]);
+
+        let lexed = &lex(br#""""a""" = 1"#).unwrap();
+        assert!(parse_key(RIter::new(lexed).as_slice(), &[Expected::Token(Token::Eq)]).is_err());
+    }
+
+    #[test]
+    fn invalid_table_name() {
+        let lexed = &lex(b"a..b]").unwrap();
+        assert!(parse_key(RIter::new(lexed).as_slice(), &[Expected::Token(Token::Sbc)]).is_err());
+        let lexed = &lex(b"a b]").unwrap();
+        assert!(parse_key(RIter::new(lexed).as_slice(), &[Expected::Token(Token::Sbc)]).is_err());
+        let lexed = &lex(b"a.]").unwrap();
+        assert!(parse_key(RIter::new(lexed).as_slice(), &[Expected::Token(Token::Sbc)]).is_err());
+    }
+}
@@ -2,7 +2,7 @@ use crate::builtins::array::parse_array;
 use crate::builtins::inline_table::parse_inline_table;
 use crate::builtins::num::{parse_num_or_date, Hint};
 use crate::builtins::string::parse_string;
-use crate::error::ErrLocation;
+use crate::error::{ErrLocation, Limit, Span};
 use crate::lexer::Token;
 use crate::parser::r_iter::RIter;
 use crate::parser::r_slice::*;
@@ -11,36 +11,68 @@ use crate::{TomlError, TomlValue};
 
 pub mod array;
 pub mod inline_table;
+pub mod key;
 pub mod num;
 pub mod string;
 
-pub fn parse_value(iter: RSlice) -> Result<ParsedValue, TomlError> {
+pub fn parse_value<'t, 'a>(iter: RSlice<'t, 'a>) -> Result<ParsedValue<'t, 'a>, TomlError<'a>> {
     let mut iter = RIter::from(iter);
 
-    while let Some((next, _)) = iter.next() {
+    // kept one token behind so errors point at the unexpected token
+    let mut previous = iter.clone();
+
+    while let Some((next, span)) = iter.next() {
         if next.is_space() {
+            previous = iter.clone();
             continue;
         }
 
         return match next {
             Token::DoubleQuote | Token::SingleQuote => parse_string(iter.as_slice(), *next),
-            Token::Sbo => parse_array(iter.as_slice()),
-            Token::Cbo => parse_inline_table(iter.as_slice()),
-            Token::Literal(x) => check_for_other_values(x.trim(), iter.as_slice()),
-            _ => {
-                dbg!(next);
-                Err(TomlError::UnspecifiedValue(ErrLocation::new(iter)))
+            Token::Sbo | Token::Cbo => {
+                let depth = iter.limits.depth;
+                let max_depth = iter.limits.options.max_depth;
+
+                if depth >= max_depth {
+                    return Err(TomlError::LimitExceeded(
+                        ErrLocation::new(previous),
+                        Limit::Depth(max_depth),
+                    ));
+                }
+
+                iter.limits.depth += 1;
+                let mut parsed = if *next == Token::Sbo {
+                    parse_array(iter.as_slice())?
+                } else {
+                    parse_inline_table(iter.as_slice())?
+                };
+                parsed.section.limits.depth = depth;
+
+                Ok(parsed)
+            }
+            // the literal has been consumed, point back at it
+            Token::Literal(x) => {
+                check_for_other_values(x.trim(), iter.as_slice()).map_err(|err| match err {
+                    TomlError::CannotParseValue(_) => {
+                        let start = span.start + x.len() - x.trim_start().len();
+                        let end = span.end - (x.len() - x.trim_end().len());
+                        let location = ErrLocation::new(previous).with_span(Span::new(start, end));
+                        TomlError::CannotParseValue(location)
+                    }
+                    err => err,
+                })
             }
+            _ => Err(TomlError::UnspecifiedValue(ErrLocation::new(previous))),
         };
     }
 
-    Ok(ParsedValue::new(TomlValue::Int(0), iter))
+    Err(TomlError::UnspecifiedValue(ErrLocation::new(iter)))
 }
 
-pub fn check_for_other_values<'a>(
+pub fn check_for_other_values<'t, 'a>(
     literal: &'a str,
-    slice: RSlice<'a>,
-) -> Result<ParsedValue<'a>, TomlError<'a>> {
+    slice: RSlice<'t, 'a>,
+) -> Result<ParsedValue<'t, 'a>, TomlError<'a>> {
     match literal {
         "inf" => Ok(ParsedValue::new(
             TomlValue::Float(f64::INFINITY),
@@ -65,7 +97,7 @@ pub fn check_for_other_values<'a>(
                 match first_letter {
                     '+' => parse_num_or_date(literal, Hint::Positive, slice),
                     '-' => parse_num_or_date(literal, Hint::Negative, slice),
-                    n if n.is_digit(10) => parse_num_or_date(literal, Hint::Number, slice),
+                    n if n.is_ascii_digit() => parse_num_or_date(literal, Hint::Number, slice),
                     _ => Err(TomlError::CannotParseValue(ErrLocation::new(RIter::from(
                         slice,
                     )))),
@@ -82,9 +114,10 @@ pub fn check_for_other_values<'a>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lexer::Span;
 
-    pub fn get_tokens_from_literal(literal: &str) -> RSlice {
-        let vec = vec![(Token::Literal(literal), literal.len())];
+    pub fn get_tokens_from_literal(literal: &str) -> RSlice<'_, '_> {
+        let vec = vec![(Token::Literal(literal), Span::new(0, literal.len()))];
         RIter::new(vec.leak()).as_slice()
     }
 }
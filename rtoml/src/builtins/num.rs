@@ -8,76 +8,38 @@ use speedate;
 
 #[derive(PartialEq)]
 pub enum Hint {
-    Inf,
-    Nan,
     Number,
     Negative,
     Positive,
 }
 
-pub fn parse_num_or_date<'a>(
+pub fn parse_num_or_date<'t, 'a>(
     literal: &str,
     hint: Hint,
-    slice: RSlice<'a>,
-) -> Result<ParsedValue<'a>, TomlError<'a>> {
-    let literal = literal.replace('_', "");
-    let check = check_if_nan_or_inf(&literal[1..].as_bytes());
-
-    if hint == Hint::Negative && check == Some(Hint::Inf) {
-        return Ok(ParsedValue::new(
-            TomlValue::Float(f64::INFINITY),
-            RIter::from(slice),
-        ));
-    }
-
-    let value = match hint {
-        Hint::Inf => TomlValue::Float(f64::INFINITY),
-        Hint::Nan => TomlValue::Float(f64::NAN),
-        Hint::Number => {
-            if let Some(integer) = get_integer(&literal) {
-                TomlValue::Int(integer)
-            } else if let Ok(float) = literal.parse() {
-                TomlValue::Float(float)
-            } else if let Ok(parsed_date_time) = speedate::DateTime::parse_str(&literal) {
-                TomlValue::DateTime(DateTime::DateTime(parsed_date_time))
-            } else if let Ok(parsed_date) = speedate::Date::parse_str(&literal) {
-                TomlValue::DateTime(DateTime::Date(parsed_date))
-            } else if let Ok(time) = speedate::Time::parse_str(&literal) {
-                TomlValue::DateTime(DateTime::Time(time))
-            } else {
-                return Err(TomlError::CannotParseValue(ErrLocation::new(RIter::from(
-                    slice,
-                ))));
-            }
-        }
-        Hint::Positive | Hint::Negative => {
-            match check {
-                // if it's negative and in, we return early
-                Some(Hint::Inf) => TomlValue::Float(f64::INFINITY),
-                Some(Hint::Nan) => TomlValue::Float(f64::NAN),
-                _ => {
-                    if let Some(integer) = get_integer(&literal) {
-                        TomlValue::Int(integer)
-                    } else if let Ok(float) = literal.parse() {
-                        TomlValue::Float(float)
-                    } else {
-                        return Err(TomlError::CannotParseValue(ErrLocation::new(RIter::from(
-                            slice,
-                        ))));
-                    }
-                }
-            }
-        }
+    slice: RSlice<'t, 'a>,
+) -> Result<ParsedValue<'t, 'a>, TomlError<'a>> {
+    let unsigned = if hint == Hint::Number {
+        literal
+    } else {
+        &literal[1..]
     };
 
-    Ok(ParsedValue::new(value, RIter::from(slice)))
-}
-
-fn check_if_nan_or_inf(literal: &[u8]) -> Option<Hint> {
-    match literal {
-        b"inf" => Some(Hint::Inf),
-        b"nan" => Some(Hint::Nan),
+    let value = match unsigned {
+        "inf" if hint == Hint::Negative => Some(TomlValue::Float(f64::NEG_INFINITY)),
+        "inf" => Some(TomlValue::Float(f64::INFINITY)),
+        "nan" => Some(TomlValue::Float(f64::NAN)),
+        // an integer out of range is an error rather than a float
+        _ if is_integer(literal) => get_integer(&literal.replace('_', "")).map(TomlValue::Int),
+        _ if is_float(literal) => literal.replace('_', "").parse().ok().map(TomlValue::Float),
+        _ if hint == Hint::Number && is_date_time(literal) => get_date_time(literal),
         _ => None,
+    };
+
+    match value {
+        Some(value) => Ok(ParsedValue::new(value, RIter::from(slice))),
+        None => Err(TomlError::CannotParseValue(ErrLocation::new(RIter::from(
+            slice,
+        )))),
     }
 }
 
@@ -87,14 +49,129 @@ fn get_integer(literal: &str) -> Option<i64> {
 
     // unwrap here is ok since literal.get(0..2) is not None
     match prefix {
-        Some("0b") => i64::from_str_radix(&un_prefixed_literal.unwrap(), 2),
-        Some("0x") => i64::from_str_radix(&un_prefixed_literal.unwrap(), 16),
-        Some("0o") => i64::from_str_radix(&un_prefixed_literal.unwrap(), 8),
-        _ => i64::from_str_radix(literal, 10),
+        Some("0b") => i64::from_str_radix(un_prefixed_literal.unwrap(), 2),
+        Some("0x") => i64::from_str_radix(un_prefixed_literal.unwrap(), 16),
+        Some("0o") => i64::from_str_radix(un_prefixed_literal.unwrap(), 8),
+        _ => literal.parse::<i64>(),
     }
     .ok()
 }
 
+fn get_date_time<'a>(literal: &str) -> Option<TomlValue<'a>> {
+    if let Ok(date_time) = speedate::DateTime::parse_str(literal) {
+        Some(TomlValue::DateTime(DateTime::DateTime(date_time)))
+    } else if let Ok(date) = speedate::Date::parse_str(literal) {
+        Some(TomlValue::DateTime(DateTime::Date(date)))
+    } else if let Ok(time) = speedate::Time::parse_str(literal) {
+        Some(TomlValue::DateTime(DateTime::Time(time)))
+    } else {
+        None
+    }
+}
+
+// digits of `radix` where every underscore is surrounded by digits
+fn is_digits(literal: &str, radix: u32) -> bool {
+    !literal.is_empty()
+        && !literal.starts_with('_')
+        && !literal.ends_with('_')
+        && !literal.contains("__")
+        && literal.chars().all(|x| x == '_' || x.is_digit(radix))
+}
+
+// decimal integers can't have leading zeros, `0x`, `0o` and `0b` integers
+// can't have a sign
+// https://toml.io/en/v1.0.0#integer
+fn is_integer(literal: &str) -> bool {
+    match literal.get(0..2) {
+        Some("0x") => is_digits(&literal[2..], 16),
+        Some("0o") => is_digits(&literal[2..], 8),
+        Some("0b") => is_digits(&literal[2..], 2),
+        _ => is_decimal(literal.strip_prefix(['+', '-']).unwrap_or(literal)),
+    }
+}
+
+fn is_decimal(literal: &str) -> bool {
+    is_digits(literal, 10) && (literal == "0" || !literal.starts_with('0'))
+}
+
+// an integer part followed by a fractional part, an exponent or both
+// https://toml.io/en/v1.0.0#float
+fn is_float(literal: &str) -> bool {
+    let unsigned = literal.strip_prefix(['+', '-']).unwrap_or(literal);
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(index) => (&unsigned[..index], Some(&unsigned[index + 1..])),
+        None => (unsigned, None),
+    };
+    let (integer, fraction) = match mantissa.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (mantissa, None),
+    };
+
+    (fraction.is_some() || exponent.is_some())
+        && is_decimal(integer)
+        && fraction.is_none_or(|x| is_digits(x, 10))
+        && exponent.is_none_or(|x| is_digits(x.strip_prefix(['+', '-']).unwrap_or(x), 10))
+}
+
+// the shape of `1979-05-27`, `07:32:00(.999)` or a date and time separated by
+// `T` or a space with an optional `Z` or `+07:00` offset, seconds included
+// https://toml.io/en/v1.0.0#offset-date-time
+fn is_date_time(literal: &str) -> bool {
+    let bytes = literal.as_bytes();
+
+    if bytes.len() >= 10 && is_date(&bytes[..10]) {
+        match bytes.get(10) {
+            None => true,
+            Some(b'T' | b't' | b' ') => is_time_and_offset(&bytes[11..]),
+            Some(_) => false,
+        }
+    } else {
+        is_time(bytes)
+    }
+}
+
+fn is_date(bytes: &[u8]) -> bool {
+    is_pattern(bytes, b"dddd-dd-dd")
+}
+
+fn is_time(bytes: &[u8]) -> bool {
+    if bytes.len() < 8 {
+        return false;
+    }
+
+    let (time, fraction) = bytes.split_at(8);
+
+    is_pattern(time, b"dd:dd:dd")
+        && match fraction {
+            [] => true,
+            [b'.', digits @ ..] => !digits.is_empty() && digits.iter().all(u8::is_ascii_digit),
+            _ => false,
+        }
+}
+
+fn is_time_and_offset(bytes: &[u8]) -> bool {
+    let offset = bytes
+        .iter()
+        .position(|x| matches!(x, b'Z' | b'z' | b'+' | b'-'))
+        .unwrap_or(bytes.len());
+
+    is_time(&bytes[..offset])
+        && match &bytes[offset..] {
+            [] | [b'Z' | b'z'] => true,
+            [b'+' | b'-', rest @ ..] => is_pattern(rest, b"dd:dd"),
+            _ => false,
+        }
+}
+
+// `d` stands for any digit, anything else has to match exactly
+fn is_pattern(bytes: &[u8], pattern: &[u8]) -> bool {
+    bytes.len() == pattern.len()
+        && bytes.iter().zip(pattern).all(|(x, p)| match p {
+            b'd' => x.is_ascii_digit(),
+            p => x == p,
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use crate::builtins::{parse_value, tests::get_tokens_from_literal};
@@ -106,7 +183,7 @@ mod tests {
     pub fn floats() {
         // floats with underscore
         assert_eq!(
-            parse_value(get_tokens_from_literal("1_2_3_4.1_2_3_"))
+            parse_value(get_tokens_from_literal("1_2_3_4.1_2_3"))
                 .unwrap()
                 .value,
             TomlValue::Float(1234.123)
@@ -289,4 +366,66 @@ mod tests {
             TomlValue::Int(-1)
         );
     }
+
+    #[test]
+    pub fn invalid_numbers() {
+        for literal in [
+            "01",
+            "+01",
+            "+-1",
+            "1__2",
+            "_1",
+            "1_",
+            "0x",
+            "+0x1",
+            "0xG",
+            "0b2",
+            "0X1",
+            "03.14",
+            "1.",
+            ".1",
+            "1.2_",
+            "1._2",
+            "1e",
+            "1e_2",
+            "1.e2",
+            "+nan_",
+            "nanx",
+            "-infx",
+            "9223372036854775808",
+            "1987-07-05T17:45Z",
+            "1987-07-05T17:45:00+7:00",
+            "07:32",
+            "1987-07-05X",
+            "12:00:00.",
+        ] {
+            assert!(
+                parse_value(get_tokens_from_literal(literal)).is_err(),
+                "{}",
+                literal
+            );
+        }
+
+        for literal in [
+            "0",
+            "+0",
+            "-0",
+            "0.0",
+            "1e06",
+            "1E+2",
+            "6.626e-34",
+            "0xdead_beef",
+            "0o0_7",
+            "0b1",
+            "-9223372036854775808",
+            "1979-05-27t07:32:00z",
+            "1979-05-27 07:32:00+07:00",
+        ] {
+            assert!(
+                parse_value(get_tokens_from_literal(literal)).is_ok(),
+                "{}",
+                literal
+            );
+        }
+    }
 }
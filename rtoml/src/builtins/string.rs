@@ -1,88 +1,136 @@
-use crate::error::ErrLocation;
+use crate::error::{ErrLocation, Expected, Limit, Span};
 use crate::lexer::Token;
 use crate::parser::r_iter::RIter;
 use crate::parser::r_slice::RSlice;
 use crate::parser::ParsedValue;
 use crate::{TomlError, TomlValue};
 
-pub fn parse_string<'a>(
-    slice: RSlice<'a>,
+// `slice` starts right after the first quote
+pub fn parse_string<'t, 'a>(
+    slice: RSlice<'t, 'a>,
     quote_type: Token,
-) -> Result<ParsedValue<'a>, TomlError<'a>> {
+) -> Result<ParsedValue<'t, 'a>, TomlError<'a>> {
+    let parsed = parse_string_content(slice, quote_type)?;
+    let max_length = slice.limits.options.max_string_length;
+
+    match &parsed.value {
+        TomlValue::String(string) if string.len() > max_length => {
+            // from the opening quote to the closing one
+            let span = Span::new(
+                slice.index.offset.saturating_sub(1),
+                parsed.section.index.offset,
+            );
+
+            Err(TomlError::LimitExceeded(
+                ErrLocation::new(RIter::from(slice)).with_span(span),
+                Limit::StringLength(max_length),
+            ))
+        }
+        _ => Ok(parsed),
+    }
+}
+
+fn parse_string_content<'t, 'a>(
+    slice: RSlice<'t, 'a>,
+    quote_type: Token,
+) -> Result<ParsedValue<'t, 'a>, TomlError<'a>> {
     let mut iter = RIter::from(slice);
     let mut string = String::new();
     let mut is_multiline = false;
 
-    if iter.next_if_eq(quote_type) && iter.next_if_eq(quote_type) {
-        is_multiline = true;
+    if iter.next_if_eq(quote_type) {
+        if iter.next_if_eq(quote_type) {
+            is_multiline = true;
 
-        iter.next_if_eq(Token::LineBreak);
+            iter.next_if_eq(Token::LineBreak);
+        } else {
+            // `""` or `''`, the closing quote was just consumed
+            return Ok(ParsedValue::new(TomlValue::String(string), iter));
+        }
     }
 
     if quote_type == Token::SingleQuote {
-        parse_string_single_quotes(is_multiline, &mut string, &mut iter);
+        parse_string_single_quotes(is_multiline, &mut string, &mut iter)?;
         return Ok(ParsedValue::new(TomlValue::String(string), iter));
     }
 
     while let Some((token, _)) = iter.next() {
         match token {
             Token::DoubleQuote => {
-                if is_multiline {
-                    if iter.next_if_eq(*token) && iter.next_if_eq(*token) {
-                        break;
-                    } else {
-                        string.push((*token).into());
-                        if let Some((Token::Literal(x), _)) = iter.peek() {
-                            string.push_str(x);
-                            iter.next();
-                        } else {
-                            break;
-                        }
-                    }
-                } else {
-                    break;
+                if !is_multiline || closing_quotes(*token, &mut iter, &mut string) {
+                    return Ok(ParsedValue::new(TomlValue::String(string), iter));
                 }
             }
             Token::BackSlash => {
-                if let Some((token, _)) = iter.next() {
+                let escaped = iter.clone();
+
+                if let Some((token, span)) = iter.next() {
                     match token {
                         Token::Literal(literal) => {
-                            let first_char = literal.as_bytes().get(0);
-                            if first_char == Some(&b'u') {
-                                if let Some(eight_dig) = &literal.get(1..9) {
-                                    string.push(get_char_from_scalar(eight_dig, iter.as_slice())?);
-                                    string.push_str(&literal[9..]);
-                                } else if let Some(four_dig) = &literal.get(1..5) {
-                                    string.push(get_char_from_scalar(four_dig, iter.as_slice())?);
-                                    string.push_str(&literal[5..]);
-                                } else {
-                                    return Err(TomlError::UnknownEscapeSequence(
-                                        ErrLocation::new(iter),
-                                    ));
+                            let first_char = literal.as_bytes().first();
+                            // `\uXXXX` or `\UXXXXXXXX`
+                            let digits = match first_char {
+                                Some(b'u') => 4,
+                                Some(b'U') => 8,
+                                _ => 0,
+                            };
+
+                            if digits > 0 {
+                                match literal.get(1..digits + 1).and_then(char_from_scalar) {
+                                    Some(char) => {
+                                        string.push(char);
+                                        string.push_str(&literal[digits + 1..]);
+                                    }
+                                    None => {
+                                        // the digits there are, they're ascii
+                                        let len = 1 + literal
+                                            .bytes()
+                                            .skip(1)
+                                            .take(digits)
+                                            .take_while(u8::is_ascii_hexdigit)
+                                            .count();
+                                        let sequence = Token::Literal(&literal[..len]);
+
+                                        return Err(unknown_escape(
+                                            escaped,
+                                            sequence,
+                                            span.start + len,
+                                        ));
+                                    }
                                 }
-                            } else if token.is_space() {
+                            } else if token.is_space() && is_multiline {
                                 trim_till_non_whitespace(&mut iter, &mut string);
                             } else {
-                                string.push(escape(first_char, iter.as_slice())?);
-                                string.push_str(&literal[1..]);
+                                let len = literal.chars().next().map_or(0, char::len_utf8);
+
+                                match escape(first_char) {
+                                    Some(char) => string.push(char),
+                                    None => {
+                                        let sequence = Token::Literal(&literal[..len]);
+                                        return Err(unknown_escape(
+                                            escaped,
+                                            sequence,
+                                            span.start + len,
+                                        ));
+                                    }
+                                }
+                                string.push_str(&literal[len..]);
                             }
                         }
                         Token::DoubleQuote | Token::BackSlash => {
                             string.push((*token).into());
                         }
-                        Token::LineBreak => {
+                        // line ending backslash
+                        Token::LineBreak if is_multiline => {
                             trim_till_non_whitespace(&mut iter, &mut string);
                         }
-                        _ => return Err(TomlError::UnknownEscapeSequence(ErrLocation::new(iter))),
+                        _ => return Err(unknown_escape(escaped, *token, span.end)),
                     }
                 }
             }
             Token::LineBreak => {
                 if !is_multiline {
-                    return Err(TomlError::UnexpectedCharacter(
-                        ErrLocation::new(iter),
-                        &[Token::Literal(r#"'''"#)],
-                    ));
+                    return Err(unterminated(iter, quote_type, is_multiline));
                 } else {
                     string.push((*token).into());
                 }
@@ -94,90 +142,95 @@ pub fn parse_string<'a>(
         }
     }
 
-    Ok(ParsedValue::new(TomlValue::String(string), iter))
+    Err(unterminated(iter, quote_type, is_multiline))
 }
 
-fn parse_string_single_quotes(is_multiline: bool, string: &mut String, iter: &mut RIter) {
-    if is_multiline {
-        while let Some((token, _)) = iter.next() {
-            match token {
-                Token::SingleQuote => {
-                    if iter.next_if_eq(*token) && iter.next_if_eq(*token) {
-                        break;
-                    } else {
-                        string.push((*token).into());
-                        for (token, _) in iter.by_ref() {
-                            match token {
-                                Token::SingleQuote => {
-                                    string.push((*token).into());
-                                    break;
-                                }
-                                Token::Literal(x) => {
-                                    string.push_str(x);
-                                }
-                                _ => {
-                                    string.push((*token).into());
-                                }
-                            }
-                        }
-                    }
-                }
-                Token::Literal(x) => {
-                    string.push_str(x);
-                }
-                _ => {
-                    string.push((*token).into());
+fn parse_string_single_quotes<'a>(
+    is_multiline: bool,
+    string: &mut String,
+    iter: &mut RIter<'_, 'a>,
+) -> Result<(), TomlError<'a>> {
+    while let Some((token, _)) = iter.next() {
+        match token {
+            Token::SingleQuote => {
+                if !is_multiline || closing_quotes(*token, iter, string) {
+                    return Ok(());
                 }
             }
-        }
-    } else {
-        for (token, _) in iter.by_ref() {
-            match token {
-                Token::SingleQuote => break,
-                Token::Literal(x) => {
-                    string.push_str(x);
-                }
-                _ => {
-                    string.push((*token).into());
-                }
+            Token::LineBreak if !is_multiline => {
+                return Err(unterminated(iter.clone(), Token::SingleQuote, is_multiline));
+            }
+            Token::Literal(x) => {
+                string.push_str(x);
+            }
+            _ => {
+                string.push((*token).into());
             }
         }
     }
+
+    Err(unterminated(iter.clone(), Token::SingleQuote, is_multiline))
 }
 
-fn escape<'a>(char: Option<&u8>, slice: RSlice<'a>) -> Result<char, TomlError<'a>> {
-    let res = match char {
-        Some(b'b') => '\x08',
-        Some(b't') => '\x09',
-        Some(b'n') => '\x0A',
-        Some(b'f') => '\x0C',
-        Some(b'r') => '\x0D',
-        Some(_) | None => {
-            return Err(TomlError::UnknownEscapeSequence(ErrLocation::new(
-                RIter::from(slice),
-            )))
-        }
+// called after a quote of a multiline string, three quotes in a row close
+// the string and up to two more right before them are part of it
+fn closing_quotes(quote: Token, iter: &mut RIter, string: &mut String) -> bool {
+    let mut quotes = 1;
+
+    while quotes < 5 && iter.next_if_eq(quote) {
+        quotes += 1;
+    }
+
+    let is_closing = quotes >= 3;
+
+    for _ in 0..if is_closing { quotes - 3 } else { quotes } {
+        string.push(quote.into());
+    }
+
+    is_closing
+}
+
+fn unterminated<'a>(iter: RIter<'_, 'a>, quote: Token, is_multiline: bool) -> TomlError<'a> {
+    let expected: &'static [Expected] = match (quote, is_multiline) {
+        (Token::SingleQuote, false) => &[Expected::Token(Token::SingleQuote)],
+        (Token::SingleQuote, true) => &[Expected::Token(Token::Literal("'''"))],
+        (_, false) => &[Expected::Token(Token::DoubleQuote)],
+        (_, true) => &[Expected::Token(Token::Literal(r#"""""#))],
     };
 
-    Ok(res)
+    TomlError::UnexpectedCharacter(ErrLocation::new(iter), expected)
 }
 
-fn get_char_from_scalar<'a>(scalar: &str, slice: RSlice<'a>) -> Result<char, TomlError<'a>> {
+fn escape(char: Option<&u8>) -> Option<char> {
+    match char {
+        Some(b'b') => Some('\x08'),
+        Some(b't') => Some('\x09'),
+        Some(b'n') => Some('\x0A'),
+        Some(b'f') => Some('\x0C'),
+        Some(b'r') => Some('\x0D'),
+        _ => None,
+    }
+}
+
+fn char_from_scalar(scalar: &str) -> Option<char> {
     u32::from_str_radix(scalar, 16)
-        .map_err(|_| TomlError::UnknownEscapeSequence(ErrLocation::new(RIter::from(slice.clone()))))
-        .map(|byte| {
-            char::from_u32(byte).map_or_else(
-                || {
-                    Err(TomlError::UnknownEscapeSequence(ErrLocation::new(
-                        RIter::from(slice),
-                    )))
-                },
-                Ok,
-            )
-        })?
+        .ok()
+        .and_then(char::from_u32)
 }
 
-fn trim_till_non_whitespace<'a>(iter: &mut RIter<'a>, string: &mut String) {
+// the error spans from the backslash through `end`, `iter` is right after
+// the backslash and `sequence` is what follows it
+fn unknown_escape<'a>(iter: RIter<'_, 'a>, sequence: Token<'a>, end: usize) -> TomlError<'a> {
+    let backslash = iter.index.offset - 1;
+
+    TomlError::UnknownEscapeSequence(
+        ErrLocation::new(iter)
+            .with_span(Span::new(backslash, end))
+            .with_token(sequence),
+    )
+}
+
+fn trim_till_non_whitespace(iter: &mut RIter, string: &mut String) {
     while let Some((peek, _)) = iter.peek() {
         match peek {
             peek if peek.is_space() => {
@@ -199,15 +252,129 @@ fn trim_till_non_whitespace<'a>(iter: &mut RIter<'a>, string: &mut String) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::lexer::lex;
+    use crate::lexer::{lex, Span};
+
+    #[test]
+    fn empty_string() {
+        let lexed = &lex(br#"", "a""#).unwrap();
+        let mut parsed = parse_string(RIter::new(lexed).as_slice(), Token::DoubleQuote).unwrap();
+        assert_eq!(TomlValue::String(String::new()), parsed.value);
+        assert_eq!(
+            parsed.section.next(),
+            Some(&(Token::Comma, Span::new(1, 2)))
+        );
+    }
 
     #[test]
     fn basic_string() {
+        // the opening quote has already been consumed by `parse_value`
         let lexed = &lex(br#"""hello""""#).unwrap();
         let parsed = parse_string(RIter::new(lexed).as_slice(), Token::DoubleQuote);
         assert_eq!(
-            TomlValue::String(String::from(r#"tes"t"#)),
+            TomlValue::String(String::from("hello")),
             parsed.unwrap().value
         );
     }
+
+    #[test]
+    fn unicode_escapes() {
+        for (string, expected) in [
+            (r#"\u00e9abcdef""#, "éabcdef"),
+            (r#"\U0001F600 a""#, "😀 a"),
+            (r#"\u0001\U00000002""#, "\u{1}\u{2}"),
+        ] {
+            let lexed = &lex(string.as_bytes()).unwrap();
+            let parsed = parse_string(RIter::new(lexed).as_slice(), Token::DoubleQuote).unwrap();
+            assert_eq!(parsed.value, TomlValue::String(String::from(expected)));
+        }
+
+        for string in [r#"\u00e""#, r#"\U0001F60""#, r#"\uD800""#, r#"\U00110000""#] {
+            let lexed = &lex(string.as_bytes()).unwrap();
+            assert!(parse_string(RIter::new(lexed).as_slice(), Token::DoubleQuote).is_err());
+        }
+    }
+
+    #[test]
+    fn escape_errors() {
+        for (string, message, span) in [
+            (
+                r#"a\x41""#,
+                "Unknown escape sequence `\\x`",
+                Span::new(1, 3),
+            ),
+            (r#"\é""#, "Unknown escape sequence `\\é`", Span::new(0, 3)),
+            (
+                r#"\u00e""#,
+                "Unknown escape sequence `\\u00e`",
+                Span::new(0, 5),
+            ),
+            (
+                r#"\uD800""#,
+                "Unknown escape sequence `\\uD800`",
+                Span::new(0, 6),
+            ),
+            (
+                r#"\U0011ffff""#,
+                "Unknown escape sequence `\\U0011ffff`",
+                Span::new(0, 10),
+            ),
+            (
+                r#"\ud83dx""#,
+                "Unknown escape sequence `\\ud83d`",
+                Span::new(0, 6),
+            ),
+            (r#"\'""#, "Unknown escape sequence `\\'`", Span::new(0, 2)),
+            (
+                "\"\"\na\\x\n\"\"\"",
+                "Unknown escape sequence `\\x`",
+                Span::new(4, 6),
+            ),
+            (
+                "a\\\nb\"",
+                "Unknown escape sequence `\\` followed by a new line",
+                Span::new(1, 3),
+            ),
+        ] {
+            let lexed = &lex(string.as_bytes()).unwrap();
+            let err = match parse_string(RIter::new(lexed).as_slice(), Token::DoubleQuote) {
+                Err(err) => err,
+                Ok(_) => panic!("{:?} was accepted", string),
+            };
+            assert_eq!(err.message(), message, "{:?}", string);
+            assert_eq!(err.span(), Some(span), "{:?}", string);
+        }
+    }
+
+    #[test]
+    fn unterminated_strings() {
+        for string in [
+            "hello",
+            "hello\nworld\"",
+            "\"\"hello\"\"",
+            "\"\"hello\"\"\n",
+            "hello \\\nworld\"",
+        ] {
+            let lexed = &lex(string.as_bytes()).unwrap();
+            assert!(parse_string(RIter::new(lexed).as_slice(), Token::DoubleQuote).is_err());
+        }
+
+        for string in ["hello", "hello\nworld'", "''hello''"] {
+            let lexed = &lex(string.as_bytes()).unwrap();
+            assert!(parse_string(RIter::new(lexed).as_slice(), Token::SingleQuote).is_err());
+        }
+    }
+
+    #[test]
+    fn multiline_quotes() {
+        for (string, quote, expected) in [
+            (r#"""a"b""c""""#, Token::DoubleQuote, r#"a"b""c"#),
+            (r#"""a""""""#, Token::DoubleQuote, r#"a"""#),
+            ("''a'b''c'''", Token::SingleQuote, "a'b''c"),
+            ("''\na\\b\n'''''", Token::SingleQuote, "a\\b\n''"),
+        ] {
+            let lexed = &lex(string.as_bytes()).unwrap();
+            let parsed = parse_string(RIter::new(lexed).as_slice(), quote).unwrap();
+            assert_eq!(parsed.value, TomlValue::String(String::from(expected)));
+        }
+    }
 }
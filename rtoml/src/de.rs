@@ -0,0 +1,559 @@
+use crate::emitter::format_key;
+use crate::error::{OwnedTomlError, Segment};
+use crate::{DateTime, TomlError, TomlKey, TomlValue};
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::fmt::Display;
+use std::iter;
+use std::str::FromStr;
+use std::vec;
+
+use serde::de::value::StringDeserializer;
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use serde::{forward_to_deserialize_any, Deserialize, Deserializer};
+
+// parse `str` and deserialize the resulting table into `T`
+pub fn from_str<T: DeserializeOwned>(str: &str) -> Result<T, Error> {
+    T::deserialize(TomlValue::try_from(str)?)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    // the input isn't a valid document, boxed as every call of a
+    // deserializer returns this error
+    Parse(Box<OwnedTomlError>),
+    // the value at `key` doesn't fit the type it's read into, the key is
+    // empty for the document itself
+    Value { key: Vec<Segment>, message: String },
+}
+
+impl Error {
+    // path of the value that failed, empty for parse errors
+    pub fn key(&self) -> &[Segment] {
+        match self {
+            Error::Parse(_) => &[],
+            Error::Value { key, .. } => key,
+        }
+    }
+
+    // the key written like `servers[1]."host name"`, the way `FromTomlError`
+    // writes its path with the keys quoted when they need to be
+    pub fn path(&self) -> String {
+        let mut path = String::new();
+
+        for segment in self.key() {
+            match segment {
+                Segment::Key(key) => {
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(&format_key(&TomlKey::from(key.as_str())));
+                }
+                Segment::Index(index) => path.push_str(&format!("[{}]", index)),
+            }
+        }
+
+        path
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Error::Parse(err) => err.message(),
+            Error::Value { message, .. } => message,
+        }
+    }
+
+    // errors bubble up through the tables they're in, each one adds its key
+    fn at(mut self, parent: &str) -> Self {
+        if let Error::Value { key, .. } = &mut self {
+            key.insert(0, Segment::Key(parent.to_string()));
+        }
+
+        self
+    }
+
+    fn at_index(mut self, index: usize) -> Self {
+        if let Error::Value { key, .. } = &mut self {
+            key.insert(0, Segment::Index(index));
+        }
+
+        self
+    }
+}
+
+impl From<TomlError<'_>> for Error {
+    fn from(err: TomlError<'_>) -> Self {
+        Error::Parse(Box::new(OwnedTomlError::from(err)))
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(err) => Display::fmt(err, f),
+            Error::Value { key, message } if key.is_empty() => f.write_str(message),
+            Error::Value { message, .. } => write!(f, "{} for key `{}`", message, self.path()),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Value {
+            key: Vec::new(),
+            message: msg.to_string(),
+        }
+    }
+
+    // points at the field itself rather than the table missing it
+    fn missing_field(field: &'static str) -> Self {
+        Error::Value {
+            key: vec![Segment::Key(field.to_string())],
+            message: String::from("missing field"),
+        }
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for TomlValue<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            TomlValue::Int(x) => visitor.visit_i64(x),
+            TomlValue::Float(x) => visitor.visit_f64(x),
+            TomlValue::String(x) => visitor.visit_string(x),
+            TomlValue::Boolean(x) => visitor.visit_bool(x),
+            TomlValue::DateTime(x) => visitor.visit_string(x.to_string()),
+            TomlValue::Array(x) => {
+                let len = x.len();
+                let mut seq = ArrayAccess {
+                    iter: x.into_iter().enumerate(),
+                };
+                let value = visitor.visit_seq(&mut seq)?;
+
+                match seq.iter.len() {
+                    0 => Ok(value),
+                    remaining => Err(de::Error::invalid_length(
+                        len,
+                        &format!("{} values in the array", len - remaining).as_str(),
+                    )),
+                }
+            }
+            TomlValue::Table(x) => visitor.visit_map(TableAccess {
+                iter: x.into_iter(),
+                value: None,
+            }),
+        }
+    }
+
+    // a value that is present is always `Some`, missing keys are handled by
+    // the derived implementations
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // enums are externally tagged, unit variants are written as a string
+    // and all others as a table with the variant name as its only key
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self {
+            TomlValue::String(variant) => visitor.visit_enum(Enum {
+                variant,
+                value: None,
+            }),
+            TomlValue::Table(table) if table.len() == 1 => {
+                // checked that the table has exactly one entry
+                let (key, value) = table.into_iter().next().unwrap();
+                visitor.visit_enum(Enum {
+                    variant: key.to_string(),
+                    value: Some(value),
+                })
+            }
+            _ => Err(de::Error::custom(
+                "expected a string or a table with a single key for an enum",
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+impl<'de, 'a> IntoDeserializer<'de, Error> for TomlValue<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for TomlKey<'_> {
+    type Deserializer = StringDeserializer<Error>;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self.to_string().into_deserializer()
+    }
+}
+
+// hands out the entries of a table, errors of a value get its key added
+struct TableAccess<'a> {
+    iter: indexmap::map::IntoIter<TomlKey<'a>, TomlValue<'a>>,
+    value: Option<(String, TomlValue<'a>)>,
+}
+
+impl<'de, 'a> MapAccess<'de> for TableAccess<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                let key = key.to_string();
+                let parsed = seed
+                    .deserialize(key.clone().into_deserializer())
+                    .map_err(|err: Error| err.at(&key))?;
+                self.value = Some((key, value));
+                Ok(Some(parsed))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let (key, value) = self
+            .value
+            .take()
+            .ok_or_else(|| <Error as de::Error>::custom("value requested before its key"))?;
+
+        seed.deserialize(value).map_err(|err| err.at(&key))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+// hands out the values of an array, errors of a value get its index added
+struct ArrayAccess<'a> {
+    iter: iter::Enumerate<vec::IntoIter<TomlValue<'a>>>,
+}
+
+impl<'de, 'a> SeqAccess<'de> for ArrayAccess<'a> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        match self.iter.next() {
+            Some((index, value)) => seed
+                .deserialize(value)
+                .map(Some)
+                .map_err(|err| err.at_index(index)),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct Enum<'a> {
+    variant: String,
+    value: Option<TomlValue<'a>>,
+}
+
+impl<'de, 'a> EnumAccess<'de> for Enum<'a> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Self::Error> {
+        let variant: StringDeserializer<Error> = self.variant.clone().into_deserializer();
+        Ok((seed.deserialize(variant)?, self))
+    }
+}
+
+impl<'de, 'a> VariantAccess<'de> for Enum<'a> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        match self.value {
+            None => Ok(()),
+            Some(TomlValue::Table(table)) if table.is_empty() => Ok(()),
+            Some(_) => Err(de::Error::custom(format!(
+                "expected unit variant {}",
+                self.variant
+            ))),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        let variant = self.variant.clone();
+        seed.deserialize(self.value()?)
+            .map_err(|err| err.at(&variant))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let variant = self.variant.clone();
+        self.value()?
+            .deserialize_seq(visitor)
+            .map_err(|err| err.at(&variant))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let variant = self.variant.clone();
+        self.value()?
+            .deserialize_map(visitor)
+            .map_err(|err| err.at(&variant))
+    }
+}
+
+impl<'a> Enum<'a> {
+    fn value(self) -> Result<TomlValue<'a>, Error> {
+        let variant = self.variant;
+        self.value
+            .ok_or_else(|| de::Error::custom(format!("expected a table for variant {}", variant)))
+    }
+}
+
+// date-times are handed to visitors as strings
+impl<'de> Deserialize<'de> for DateTime {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DateTimeVisitor;
+
+        impl Visitor<'_> for DateTimeVisitor {
+            type Value = DateTime;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a TOML date-time, local date or local time")
+            }
+
+            fn visit_str<E: de::Error>(self, str: &str) -> Result<Self::Value, E> {
+                DateTime::from_str(str).map_err(|err| E::custom(err.to_string()))
+            }
+        }
+
+        deserializer.deserialize_str(DateTimeVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Config {
+        title: String,
+        debug: Option<bool>,
+        owner: Option<Owner>,
+        servers: BTreeMap<String, Server>,
+        ports: Vec<u16>,
+        mode: Mode,
+        backend: Backend,
+        timeout: Seconds,
+        released: DateTime,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Owner {
+        name: String,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Server {
+        ip: String,
+        weight: f64,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Mode {
+        Fast,
+        Slow,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Backend {
+        File(String),
+        Remote { host: String, port: u16 },
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Seconds(u32);
+
+    #[test]
+    fn deserialize_struct() {
+        let toml = r#"
+title = "example"
+ports = [8000, 8001]
+mode = "Fast"
+timeout = 30
+released = 1979-05-27T07:32:00Z
+backend.Remote = { host = "example.org", port = 22 }
+
+[servers.alpha]
+ip = "10.0.0.1"
+weight = 0.5
+
+[servers.beta]
+ip = "10.0.0.2"
+weight = 1.5
+"#;
+        let config: Config = from_str(toml).unwrap();
+        let mut servers = BTreeMap::new();
+        servers.insert(
+            String::from("alpha"),
+            Server {
+                ip: String::from("10.0.0.1"),
+                weight: 0.5,
+            },
+        );
+        servers.insert(
+            String::from("beta"),
+            Server {
+                ip: String::from("10.0.0.2"),
+                weight: 1.5,
+            },
+        );
+
+        assert_eq!(
+            config,
+            Config {
+                title: String::from("example"),
+                debug: None,
+                owner: None,
+                servers,
+                ports: vec![8000, 8001],
+                mode: Mode::Fast,
+                backend: Backend::Remote {
+                    host: String::from("example.org"),
+                    port: 22
+                },
+                timeout: Seconds(30),
+                released: DateTime::from_str("1979-05-27T07:32:00Z").unwrap(),
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_value() {
+        let value = TomlValue::try_from("File = \"/tmp/db\"").unwrap();
+        assert_eq!(
+            Backend::deserialize(value).unwrap(),
+            Backend::File(String::from("/tmp/db"))
+        );
+
+        let value = TomlValue::try_from("name = \"Tom\"").unwrap();
+        assert_eq!(
+            Option::<Owner>::deserialize(value).unwrap(),
+            Some(Owner {
+                name: String::from("Tom")
+            })
+        );
+    }
+
+    #[test]
+    fn deserialize_errors() {
+        let err = from_str::<Owner>("name = 1").unwrap_err();
+        assert_eq!(err.key(), [Segment::Key(String::from("name"))]);
+        assert_eq!(
+            err.to_string(),
+            "invalid type: integer `1`, expected a string for key `name`"
+        );
+
+        let err = from_str::<Owner>("title = \"Tom\"").unwrap_err();
+        assert_eq!(err.path(), "name");
+        assert_eq!(err.to_string(), "missing field for key `name`");
+
+        let err = from_str::<BTreeMap<String, u8>>("a = 256").unwrap_err();
+        assert_eq!(err.path(), "a");
+        assert_eq!(err.message(), "invalid value: integer `256`, expected u8");
+
+        let err = from_str::<BTreeMap<String, Mode>>("a = \"Medium\"").unwrap_err();
+        assert_eq!(err.path(), "a");
+        assert_eq!(
+            err.message(),
+            "unknown variant `Medium`, expected `Fast` or `Slow`"
+        );
+
+        // the path goes through every table and variant on the way
+        let err = from_str::<BTreeMap<String, Backend>>("a.Remote = { host = 1 }").unwrap_err();
+        assert_eq!(err.path(), "a.Remote.host");
+        let err = from_str::<BTreeMap<String, Server>>("\"a.b\" = { ip = \"\" }").unwrap_err();
+        assert_eq!(err.to_string(), "missing field for key `\"a.b\".weight`");
+
+        // and the index of the value in an array
+        #[derive(Debug, Deserialize)]
+        struct Servers {
+            #[allow(dead_code)]
+            servers: Vec<Server>,
+        }
+
+        let toml = "[[servers]]\nip = \"a\"\nweight = 1.0\n[[servers]]\nip = 2\nweight = 1.0\n";
+        let err = from_str::<Servers>(toml).unwrap_err();
+        assert_eq!(
+            err.key(),
+            [
+                Segment::Key(String::from("servers")),
+                Segment::Index(1),
+                Segment::Key(String::from("ip"))
+            ]
+        );
+        assert_eq!(
+            err.to_string(),
+            "invalid type: integer `2`, expected a string for key `servers[1].ip`"
+        );
+
+        let err = from_str::<BTreeMap<String, (u8, u8)>>("a = [1, 2, 3]").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid length 3, expected 2 values in the array for key `a`"
+        );
+
+        let err = from_str::<Owner>("name = ").unwrap_err();
+        assert!(matches!(err, Error::Parse(_)));
+        assert!(err.key().is_empty());
+    }
+}
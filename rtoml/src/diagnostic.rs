@@ -0,0 +1,251 @@
+use crate::emitter::format_string;
+use crate::error::{render, LineIndex, Span, TomlError};
+
+use std::fmt;
+use std::fmt::{Display, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// a problem found in a document, unlike a `TomlError` it doesn't stop the
+// parser and owns its message
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    // stable identifier, see `ErrorKind::code`
+    pub code: &'static str,
+    pub message: String,
+    pub span: Option<Span>,
+    // first definition of a duplicated name
+    pub original: Option<Span>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>, span: Option<Span>) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            span,
+            original: None,
+        }
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>, span: Option<Span>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            code,
+            message: message.into(),
+            span,
+            original: None,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    // same as `TomlError::render`, prefixed with the severity
+    pub fn render(&self, source: &str) -> String {
+        render(
+            &self.severity.to_string(),
+            self.code,
+            &self.message,
+            self.span,
+            self.original,
+            source,
+        )
+    }
+
+    // a json object for editors and CI, lines and columns start at 1, the
+    // column is counted in bytes and in utf-16 code units
+    // {"severity":"error","code":"E0002","message":"..","span":{"start":6,
+    // "end":9},"line":2,"column":4,"utf16_column":4}, duplicates also have an
+    // "original" span
+    pub fn to_json(&self, source: &str) -> String {
+        self.json(&LineIndex::new(source))
+    }
+
+    fn json(&self, index: &LineIndex) -> String {
+        let mut out = format!(
+            "{{\"severity\":\"{}\",\"code\":\"{}\",\"message\":{}",
+            self.severity,
+            self.code,
+            format_string(&self.message)
+        );
+
+        match self.span {
+            Some(span) => {
+                let position = index.position(span.start);
+                let _ = write!(
+                    out,
+                    ",\"span\":{},\"line\":{},\"column\":{},\"utf16_column\":{}",
+                    span_json(span),
+                    position.line + 1,
+                    position.column + 1,
+                    position.utf16_column + 1
+                );
+            }
+            None => {
+                out.push_str(",\"span\":null,\"line\":null,\"column\":null,\"utf16_column\":null")
+            }
+        }
+
+        if let Some(original) = self.original {
+            let _ = write!(out, ",\"original\":{}", span_json(original));
+        }
+
+        out.push('}');
+        out
+    }
+}
+
+fn span_json(span: Span) -> String {
+    format!("{{\"start\":{},\"end\":{}}}", span.start, span.end)
+}
+
+// every diagnostic as a json array, see `Diagnostic::to_json`
+pub fn to_json(diagnostics: &[Diagnostic], source: &str) -> String {
+    let mut out = String::from("[");
+    let lines = LineIndex::new(source);
+
+    for (index, diagnostic) in diagnostics.iter().enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        out.push_str(&diagnostic.json(&lines));
+    }
+
+    out.push(']');
+    out
+}
+
+impl From<TomlError<'_>> for Diagnostic {
+    fn from(err: TomlError<'_>) -> Self {
+        Self {
+            original: err.original(),
+            ..Self::error(err.kind().code(), err.to_string(), err.span())
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_with_diagnostics, TomlKey, TomlValue};
+
+    use std::convert::TryFrom;
+
+    #[test]
+    fn collects_every_error() {
+        let source = "a = 1\n[b c]\nd = 2\ne = \"\\x\"\nf = [1, 2,\n  3]\n[g]\nh = @\ni = 3\n";
+        let (value, diagnostics) = parse_with_diagnostics(source);
+        let table = value.as_table().unwrap();
+
+        assert_eq!(diagnostics.len(), 3);
+        assert!(diagnostics.iter().all(Diagnostic::is_error));
+
+        // the line each error is reported on
+        let lines: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let rendered = diagnostic.render(source);
+                let location = rendered.lines().nth(1).unwrap().to_string();
+                location[5..location.find(':').unwrap()].to_string()
+            })
+            .collect();
+        assert_eq!(lines, ["2", "4", "8"]);
+
+        // everything around the errors is still parsed
+        assert!(table.contains_key(&TomlKey::from("a")));
+        assert!(table.contains_key(&TomlKey::from("d")));
+        assert!(table.contains_key(&TomlKey::from("f")));
+        let g = table[&TomlKey::from("g")].as_table().unwrap();
+        assert!(g.contains_key(&TomlKey::from("i")));
+        assert!(!g.contains_key(&TomlKey::from("h")));
+    }
+
+    #[test]
+    fn recovers_after_values() {
+        // the error is inside of an array spread over several lines
+        let (value, diagnostics) = parse_with_diagnostics("x = [1,\n @,\n 2]\ny = 1\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Cannot parse value `@` at line: 2, column: 2"
+        );
+        assert!(value.as_table().unwrap().contains_key(&TomlKey::from("y")));
+
+        // and inside of a multiline string, its content isn't parsed
+        let source = r#"x = """
+\q
+[a]
+z = \"\"\" ]
+"""
+y = 1
+"#;
+        let (value, diagnostics) = parse_with_diagnostics(source);
+        let table = value.as_table().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Unknown escape sequence `\\q` at line: 2, column: 1"
+        );
+        assert!(!table.contains_key(&TomlKey::from("a")));
+        assert!(table.contains_key(&TomlKey::from("y")));
+    }
+
+    #[test]
+    fn json() {
+        let source = "a = 1\n[b c]\n";
+        let (_, diagnostics) = parse_with_diagnostics(source);
+
+        assert_eq!(
+            to_json(&diagnostics, source),
+            "[{\"severity\":\"error\",\"code\":\"E0002\",\
+             \"message\":\"Unexpected `b c`, expected `]` at line: 2, column: 2\",\
+             \"span\":{\"start\":7,\"end\":10},\"line\":2,\"column\":2,\"utf16_column\":2}]"
+        );
+
+        let diagnostic = Diagnostic::warning("W0001", "a \"quoted\"\nmessage", None);
+        assert_eq!(
+            diagnostic.to_json(source),
+            "{\"severity\":\"warning\",\"code\":\"W0001\",\
+             \"message\":\"a \\\"quoted\\\"\\nmessage\",\
+             \"span\":null,\"line\":null,\"column\":null,\"utf16_column\":null}"
+        );
+        assert_eq!(to_json(&[], source), "[]");
+
+        let source = "a = 1\na = 2";
+        let (_, diagnostics) = parse_with_diagnostics(source);
+        assert!(diagnostics[0].to_json(source).ends_with(
+            "\"line\":2,\"column\":1,\"utf16_column\":1,\"original\":{\"start\":0,\"end\":1}}"
+        ));
+    }
+
+    #[test]
+    fn valid_document() {
+        let (value, diagnostics) = parse_with_diagnostics("a = 1\n[b]\nc = 2");
+
+        assert!(diagnostics.is_empty());
+        assert_eq!(value, TomlValue::try_from("a = 1\n[b]\nc = 2").unwrap());
+    }
+}
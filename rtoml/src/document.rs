@@ -0,0 +1,1342 @@
+use crate::builtins::key::{parse_dotted_key, parse_key};
+use crate::builtins::parse_value;
+use crate::emitter::{format_key, format_value, Emitter};
+use crate::error::{DocumentError, Expected, OwnedTomlError, Span, TomlError};
+use crate::lexer::{lex, Token, TokenSized};
+use crate::parser::key_span;
+use crate::parser::r_iter::RIter;
+use crate::{FormatOptions, IntoOwned, TomlKey, TomlValue};
+
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
+use std::mem;
+use std::ops::Range;
+use std::str::FromStr;
+
+// a document that can be edited without losing its formatting. Every
+// statement keeps the text it was parsed from, comments and whitespace
+// included, so whatever isn't edited is written back out byte for byte
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentMut {
+    // the key/values before the first header are in a table without one
+    tables: Vec<TableMut>,
+    // blank lines and comments after the last statement
+    trailing: String,
+    // line break of the lines added to the document, the one it already uses
+    newline: &'static str,
+    // what the document parses to, kept up to date by every edit so lookups
+    // don't parse it again
+    value: TomlValue<'static>,
+}
+
+// the comments of a key/value or a header, `leading` are the lines right
+// above it, up to a blank line, and `trailing` the one on the same line. The
+// text of a comment doesn't have the `#` and the space after it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Comments {
+    pub leading: Vec<String>,
+    pub trailing: Option<String>,
+}
+
+impl Comments {
+    pub fn is_empty(&self) -> bool {
+        self.leading.is_empty() && self.trailing.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct TableMut {
+    header: Option<Header>,
+    entries: Vec<Entry>,
+}
+
+// the text around a statement, `prefix` is made of the blank lines, comments
+// and indentation before it and `suffix` is the rest of its last line, a
+// comment and the line break
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Decor {
+    prefix: String,
+    suffix: String,
+}
+
+// `[path]` or `[[path]]`
+#[derive(Debug, Clone, PartialEq)]
+struct Header {
+    decor: Decor,
+    repr: String,
+    path: Vec<String>,
+    is_array: bool,
+}
+
+// `key = value`, `separator` is everything in between
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    decor: Decor,
+    key: String,
+    path: Vec<String>,
+    separator: String,
+    value: String,
+}
+
+impl DocumentMut {
+    pub fn parse(source: &str) -> Result<Self, DocumentError> {
+        Self::split(source).map_err(|err| DocumentError::Parse(Box::new(err.into())))
+    }
+
+    fn split(source: &str) -> Result<Self, TomlError<'_>> {
+        // only valid documents are split up, the statements below can't fail
+        let value = TomlValue::try_from(source)?.into_owned();
+
+        let lexed = lex(source.as_bytes())?;
+        let mut iter = RIter::new(&lexed);
+        let mut document = Self {
+            tables: vec![TableMut::default()],
+            trailing: String::new(),
+            newline: if source.contains("\r\n") {
+                "\r\n"
+            } else {
+                "\n"
+            },
+            value,
+        };
+        // end of the previous statement
+        let mut end = 0;
+
+        while let Some(token) = iter.peek() {
+            let start = trim_start(token);
+            let prefix = &source[end..start];
+
+            match token.0 {
+                Token::Sbo => {
+                    let (header, stop) = Header::parse(&mut iter, source, prefix, start)?;
+                    document.tables.push(TableMut {
+                        header: Some(header),
+                        entries: Vec::new(),
+                    });
+                    end = stop;
+                }
+                Token::DoubleQuote | Token::SingleQuote | Token::Literal(_)
+                    if !token.0.is_space() =>
+                {
+                    let (entry, stop) = Entry::parse(&mut iter, source, prefix, start)?;
+                    // the tables are pushed in order, the last one is the current one
+                    document.tables.last_mut().unwrap().entries.push(entry);
+                    end = stop;
+                }
+                _ => {
+                    iter.next();
+                }
+            }
+        }
+
+        document.trailing = source[end..].to_string();
+
+        Ok(document)
+    }
+
+    // the document as a value, it's kept valid by every edit
+    pub fn to_value(&self) -> TomlValue<'static> {
+        self.value.clone()
+    }
+
+    // the value at a dotted `key`, like `server.port`, tables included. Like
+    // in a document, a key under an array of tables is in its last table
+    pub fn get(&self, key: &str) -> Option<TomlValue<'static>> {
+        let path = parse_path(key).ok()?;
+
+        walk(&self.value, &path).cloned()
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    // the comments of the key/value or the table at `key`, the last one of
+    // an array of tables
+    pub fn comments_for(&self, key: &str) -> Option<Comments> {
+        let path = parse_path(key).ok()?;
+
+        if let Some((table, entry, len)) = self.locate(&path) {
+            return (len == path.len()).then(|| self.tables[table].entries[entry].decor.comments());
+        }
+
+        self.tables
+            .iter()
+            .rev()
+            .filter_map(|table| table.header.as_ref())
+            .find(|header| header.path == path)
+            .map(|header| header.decor.comments())
+    }
+
+    // the path and comments of every header and key/value, in the order
+    // they're in the document
+    pub fn comments(&self) -> Vec<(Vec<String>, Comments)> {
+        let mut comments = Vec::new();
+
+        for table in &self.tables {
+            if let Some(header) = &table.header {
+                comments.push((header.path.clone(), header.decor.comments()));
+            }
+
+            for entry in &table.entries {
+                let path = table.path().iter().chain(&entry.path).cloned().collect();
+                comments.push((path, entry.decor.comments()));
+            }
+        }
+
+        comments
+    }
+
+    // replace the value of an existing key, the key, the comments around it
+    // and the spacing of the `=` are kept
+    pub fn set(&mut self, key: &str, value: &TomlValue) -> Result<(), DocumentError> {
+        let path = parse_path(key)?;
+        let before = self.clone();
+
+        match self.locate(&path) {
+            Some((table, entry, len)) if len == path.len() => {
+                self.tables[table].entries[entry].value = format_value(value);
+            }
+            // the key is in an inline table, only its value is replaced
+            Some((table, entry, len)) => {
+                let entry = &mut self.tables[table].entries[entry];
+                let rest = &path[len..];
+                let (span, depth) = inline_table(&entry.value, rest).ok_or_else(|| missing(key))?;
+                let item = items(&entry.value, span)
+                    .into_iter()
+                    .find(|item| item.path == rest[depth..])
+                    .ok_or_else(|| missing(key))?;
+
+                entry
+                    .value
+                    .replace_range(item.value.start..item.value.end, &format_value(value));
+            }
+            None => return Err(missing(key)),
+        }
+
+        self.check(before)
+    }
+
+    // add a key that doesn't exist yet, after the last key/value of the
+    // table it belongs to, or in the inline table holding it
+    pub fn insert(&mut self, key: &str, value: &TomlValue) -> Result<(), DocumentError> {
+        let path = parse_path(key)?;
+
+        if self.contains_key(key) {
+            return Err(DocumentError::KeyExists(key.to_string()));
+        }
+
+        let before = self.clone();
+
+        if let Some((table, entry, len)) = self.locate(&path) {
+            let entry = &mut self.tables[table].entries[entry];
+            let (span, depth) =
+                inline_table(&entry.value, &path[len..]).ok_or_else(|| not_a_table(key))?;
+            let rest = &path[len + depth..];
+            let items = items(&entry.value, span);
+
+            // the key would be under a value that isn't a table
+            if items.iter().any(|item| rest.starts_with(&item.path)) {
+                return Err(not_a_table(key));
+            }
+
+            let text = format!("{} = {}", join(rest), format_value(value));
+
+            match items.last() {
+                Some(item) => entry
+                    .value
+                    .insert_str(item.value.end, &format!(", {}", text)),
+                // `{}` or `{ }`
+                None => entry
+                    .value
+                    .replace_range(span.start + 1..span.end - 1, &format!(" {} ", text)),
+            }
+
+            return self.check(before);
+        }
+
+        // the table with the longest header the key is under, the last one
+        // for an array of tables
+        let (index, len) = self
+            .tables
+            .iter()
+            .enumerate()
+            .filter_map(|(index, table)| {
+                let header = table.path();
+                (header.len() < path.len() && path.starts_with(header))
+                    .then_some((index, header.len()))
+            })
+            .max_by_key(|(index, len)| (*len, *index))
+            // the root table has an empty path
+            .unwrap();
+
+        let newline = self.newline;
+        let table = &mut self.tables[index];
+        let indent = table
+            .entries
+            .last()
+            .map_or("", |entry| indentation(&entry.decor.prefix));
+        let entry = Entry {
+            decor: Decor::new(indent, newline),
+            key: join(&path[len..]),
+            path: path[len..].to_vec(),
+            separator: String::from(" = "),
+            value: format_value(value),
+        };
+
+        let previous = match (table.entries.last_mut(), &mut table.header) {
+            (Some(entry), _) => Some(&mut entry.decor),
+            (None, Some(header)) => Some(&mut header.decor),
+            (None, None) => None,
+        };
+
+        if let Some(decor) = previous {
+            end_line(&mut decor.suffix, newline);
+        }
+
+        table.entries.push(entry);
+        self.check(before)
+    }
+
+    // remove a key along with the comments above it, a table is removed
+    // with all of its sub-tables
+    pub fn remove(&mut self, key: &str) -> Result<TomlValue<'static>, DocumentError> {
+        let path = parse_path(key)?;
+        let value = self
+            .get(key)
+            .ok_or_else(|| DocumentError::NotFound(key.to_string()))?;
+        let before = self.clone();
+
+        match self.locate(&path) {
+            Some((table, entry, len)) if len < path.len() => {
+                let entry = &mut self.tables[table].entries[entry];
+                let rest = &path[len..];
+
+                // a table of dotted keys is spread over several key/values
+                while let Some((span, depth)) = inline_table(&entry.value, rest) {
+                    match removed(&entry.value, span, &rest[depth..]) {
+                        Some(range) => entry.value.replace_range(range, ""),
+                        None => break,
+                    }
+                }
+            }
+            _ => {
+                let scope = self.scope(&path);
+                let mut index = 0;
+
+                self.tables.retain(|table| {
+                    index += 1;
+                    index <= scope || table.header.is_none() || !table.path().starts_with(&path)
+                });
+
+                for table in &mut self.tables[scope..] {
+                    let header = table_path(&table.header);
+
+                    table
+                        .entries
+                        .retain(|entry| !under(header, &entry.path, &path));
+                }
+            }
+        }
+
+        self.check(before)?;
+        Ok(value)
+    }
+
+    // give the last segment of `key` a new name, keys and headers under it
+    // are renamed as well
+    pub fn rename(&mut self, key: &str, name: &str) -> Result<(), DocumentError> {
+        let path = parse_path(key)?;
+
+        if !self.contains_key(key) {
+            return Err(missing(key));
+        }
+
+        let index = path.len() - 1;
+        let mut renamed = path.clone();
+        renamed[index] = name.to_string();
+
+        if self.contains_key(&join(&renamed)) {
+            return Err(DocumentError::KeyExists(join(&renamed)));
+        }
+
+        let before = self.clone();
+
+        if let Some((table, entry, len)) = self.locate(&path).filter(|(.., len)| *len < path.len())
+        {
+            let entry = &mut self.tables[table].entries[entry];
+            let (span, depth) =
+                inline_table(&entry.value, &path[len..]).ok_or_else(|| missing(key))?;
+            let rest = &path[len + depth..];
+
+            // from the last key so the spans of the others stay right
+            for item in items(&entry.value, span).iter().rev() {
+                if item.path.starts_with(rest) {
+                    let mut renamed = item.path.clone();
+                    renamed[rest.len() - 1] = name.to_string();
+                    entry
+                        .value
+                        .replace_range(item.key.start..item.key.end, &join(&renamed));
+                }
+            }
+
+            return self.check(before);
+        }
+
+        let scope = self.scope(&path);
+
+        for table in &mut self.tables[scope..] {
+            if let Some(header) = &mut table.header {
+                if header.path.starts_with(&path) {
+                    header.path[index] = name.to_string();
+                    header.repr = if header.is_array {
+                        format!("[[{}]]", join(&header.path))
+                    } else {
+                        format!("[{}]", join(&header.path))
+                    };
+                }
+            }
+
+            let header = table_path(&table.header);
+
+            for entry in &mut table.entries {
+                // keys under a renamed header are left alone
+                if index >= header.len() && under(header, &entry.path, &path) {
+                    entry.path[index - header.len()] = name.to_string();
+                    entry.key = join(&entry.path);
+                }
+            }
+        }
+
+        self.check(before)
+    }
+
+    // add an empty `[key]` table at the end of the document
+    pub fn add_table(&mut self, key: &str) -> Result<(), DocumentError> {
+        let path = parse_path(key)?;
+
+        if self.contains_key(key) {
+            return Err(DocumentError::KeyExists(key.to_string()));
+        }
+
+        let before = self.clone();
+        let is_empty = self.to_string().trim().is_empty();
+        let newline = self.newline;
+
+        // the header goes after the comments at the end of the document
+        let mut prefix = mem::take(&mut self.trailing);
+
+        if prefix.is_empty() {
+            if let Some(decor) = self.last_decor() {
+                end_line(&mut decor.suffix, newline);
+            }
+        } else {
+            end_line(&mut prefix, newline);
+        }
+
+        if !is_empty {
+            prefix.push_str(newline);
+        }
+
+        self.tables.push(TableMut {
+            header: Some(Header {
+                decor: Decor::new(&prefix, newline),
+                repr: format!("[{}]", join(&path)),
+                path,
+                is_array: false,
+            }),
+            entries: Vec::new(),
+        });
+
+        self.check(before)
+    }
+
+    // lay the document out following `options`, comments stay with the
+    // statement they're attached to. Keys, headers and values are kept as
+    // they're written except for arrays that don't fit on their line, tables
+    // keep their order and their form so `inline_tables` doesn't apply. The
+    // comments at the top of the document stay there when keys are sorted
+    pub fn format(&mut self, options: &FormatOptions) -> Result<(), DocumentError> {
+        let before = self.clone();
+        let newline = self.newline;
+
+        for table in &mut self.tables {
+            let depth = table.path().len();
+            let indent = if options.indent_tables {
+                " ".repeat(depth.saturating_sub(1) * options.indent)
+            } else {
+                String::new()
+            };
+
+            if let Some(header) = &mut table.header {
+                header.decor.indent(&indent);
+            }
+
+            if options.sort_keys {
+                // the last key/value of a document may not end its line
+                for entry in &mut table.entries {
+                    end_line(&mut entry.decor.suffix, newline);
+                }
+
+                let top = match (&table.header, table.entries.first_mut()) {
+                    (None, Some(entry)) => mem::take(&mut entry.decor.prefix),
+                    _ => String::new(),
+                };
+
+                table.entries.sort_by_cached_key(|entry| entry.path.clone());
+
+                if let Some(entry) = table.entries.first_mut() {
+                    entry.decor.prefix.insert_str(0, &top);
+                }
+            }
+
+            let width = if options.align_entries {
+                table
+                    .entries
+                    .iter()
+                    .map(|entry| entry.key.chars().count())
+                    .max()
+            } else {
+                None
+            };
+
+            for entry in &mut table.entries {
+                let padding = width.map_or(0, |width| width - entry.key.chars().count());
+                entry.decor.indent(&indent);
+                entry.separator = format!("{} = ", " ".repeat(padding));
+
+                let column = indent.len() + entry.key.chars().count() + entry.separator.len();
+                let fits = column + entry.value.chars().count() <= options.max_width;
+
+                // arrays over multiple lines may have comments in them
+                if entry.value.starts_with('[') && !entry.value.contains('\n') && !fits {
+                    entry.value = Emitter::with_options(options).value_at(
+                        &parse_repr(&entry.value),
+                        column,
+                        &indent,
+                    );
+                }
+            }
+        }
+
+        self.check(before)
+    }
+
+    // index of the first table `path` may be in, the last `[[header]]` it's
+    // under starts the only tables it can be in
+    fn scope(&self, path: &[String]) -> usize {
+        self.tables
+            .iter()
+            .rposition(|table| match &table.header {
+                Some(header) => {
+                    header.is_array
+                        && header.path.len() < path.len()
+                        && path.starts_with(&header.path)
+                }
+                None => false,
+            })
+            .unwrap_or(0)
+    }
+
+    // the key/value whose full path `path` starts with, the length of the
+    // full path is returned along with the indices of the table and entry
+    fn locate(&self, path: &[String]) -> Option<(usize, usize, usize)> {
+        let scope = self.scope(path);
+
+        self.tables
+            .iter()
+            .enumerate()
+            .skip(scope)
+            .find_map(|(index, table)| {
+                let header = table.path();
+
+                table
+                    .entries
+                    .iter()
+                    .position(|entry| {
+                        let full = header.len() + entry.path.len();
+
+                        full <= path.len()
+                            && path.starts_with(header)
+                            && path[header.len()..full] == entry.path[..]
+                    })
+                    .map(|entry| (index, entry, header.len() + table.entries[entry].path.len()))
+            })
+    }
+
+    fn last_decor(&mut self) -> Option<&mut Decor> {
+        self.tables.iter_mut().rev().find_map(|table| {
+            match (table.entries.last_mut(), &mut table.header) {
+                (Some(entry), _) => Some(&mut entry.decor),
+                (None, Some(header)) => Some(&mut header.decor),
+                (None, None) => None,
+            }
+        })
+    }
+
+    // an edit that made the document invalid is undone, otherwise the value
+    // of the edited document is kept
+    fn check(&mut self, before: Self) -> Result<(), DocumentError> {
+        let source = self.to_string();
+
+        match TomlValue::try_from(source.as_str()) {
+            Ok(value) => {
+                self.value = value.into_owned();
+                Ok(())
+            }
+            Err(err) => {
+                let err = OwnedTomlError::from(err);
+                *self = before;
+                Err(DocumentError::Invalid(Box::new(err)))
+            }
+        }
+    }
+}
+
+impl TableMut {
+    fn path(&self) -> &[String] {
+        table_path(&self.header)
+    }
+}
+
+impl Header {
+    // the header starting at `start`, the end of its line is returned too
+    fn parse<'a>(
+        iter: &mut RIter<'_, 'a>,
+        source: &'a str,
+        prefix: &str,
+        start: usize,
+    ) -> Result<(Self, usize), TomlError<'a>> {
+        iter.next();
+
+        let is_array = iter.next_if_eq(Token::Sbo);
+        let parsed = parse_key(iter.as_slice(), &[Expected::Token(Token::Sbc)])?;
+        *iter = parsed.section;
+
+        if is_array {
+            iter.next();
+        }
+
+        let stop = iter.index.offset;
+        let end = line_end(iter, source.len());
+        let header = Self {
+            decor: Decor::new(prefix, &source[stop..end]),
+            repr: source[start..stop].to_string(),
+            path: segments(&parsed.path),
+            is_array,
+        };
+
+        Ok((header, end))
+    }
+}
+
+impl Entry {
+    // the key/value starting at `start`, the end of its line is returned too
+    fn parse<'a>(
+        iter: &mut RIter<'_, 'a>,
+        source: &'a str,
+        prefix: &str,
+        start: usize,
+    ) -> Result<(Self, usize), TomlError<'a>> {
+        let location = iter.clone();
+        let key = parse_key(iter.as_slice(), &[Expected::Token(Token::Eq)])?;
+        let key_end = key_span(location, key.section.index.offset - 1).end;
+
+        let mut value = key.section;
+        while value.peek().is_some_and(|(token, _)| token.is_space()) {
+            value.next();
+        }
+
+        // a valid key/value always has a value
+        let value_start = value.peek().map_or(source.len(), trim_start);
+        *iter = parse_value(value.as_slice())?.section;
+        let value_end = value_start + source[value_start..iter.index.offset].trim_end().len();
+        let end = line_end(iter, source.len());
+
+        let entry = Self {
+            decor: Decor::new(prefix, &source[value_end..end]),
+            key: source[start..key_end].to_string(),
+            path: segments(&key.path),
+            separator: source[key_end..value_start].to_string(),
+            value: source[value_start..value_end].to_string(),
+        };
+
+        Ok((entry, end))
+    }
+}
+
+impl Decor {
+    fn new(prefix: &str, suffix: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            suffix: suffix.to_string(),
+        }
+    }
+
+    // indent the statement and the comments above it by `indent`
+    fn indent(&mut self, indent: &str) {
+        let lines: Vec<_> = self.prefix.split('\n').collect();
+        // the last line is the one the statement is on
+        let last = lines.len() - 1;
+
+        self.prefix = lines
+            .iter()
+            .enumerate()
+            .map(|(index, line)| {
+                let text = line.trim_start_matches([' ', '\t']);
+
+                if index == last || text.starts_with('#') {
+                    format!("{}{}", indent, text)
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+
+    fn comments(&self) -> Comments {
+        // the last line of the prefix is the indentation of the statement
+        let mut leading: Vec<_> = self
+            .prefix
+            .split('\n')
+            .rev()
+            .skip(1)
+            .map_while(comment)
+            .collect();
+        leading.reverse();
+
+        Comments {
+            leading,
+            trailing: comment(self.suffix.trim()),
+        }
+    }
+}
+
+impl FromStr for DocumentMut {
+    type Err = DocumentError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Self::parse(source)
+    }
+}
+
+impl Display for DocumentMut {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for table in &self.tables {
+            if let Some(header) = &table.header {
+                f.write_str(&header.decor.prefix)?;
+                f.write_str(&header.repr)?;
+                f.write_str(&header.decor.suffix)?;
+            }
+
+            for entry in &table.entries {
+                f.write_str(&entry.decor.prefix)?;
+                f.write_str(&entry.key)?;
+                f.write_str(&entry.separator)?;
+                f.write_str(&entry.value)?;
+                f.write_str(&entry.decor.suffix)?;
+            }
+        }
+
+        f.write_str(&self.trailing)
+    }
+}
+
+// the text of `line` if it's a comment
+fn comment(line: &str) -> Option<String> {
+    let text = line.trim().strip_prefix('#')?;
+    Some(text.strip_prefix(' ').unwrap_or(text).to_string())
+}
+
+fn table_path(header: &Option<Header>) -> &[String] {
+    header.as_ref().map_or(&[], |header| &header.path)
+}
+
+// whether the key at `entry` in the table at `header` is `path` or under it
+fn under(header: &[String], entry: &[String], path: &[String]) -> bool {
+    header.iter().chain(entry).take(path.len()).eq(path)
+}
+
+// where a token starts once the whitespace in front of it is skipped
+fn trim_start((token, span): &TokenSized) -> usize {
+    match token {
+        Token::Literal(x) => span.start + x.len() - x.trim_start().len(),
+        _ => span.start,
+    }
+}
+
+// move past the line break ending the current line, where the next line
+// starts is returned
+fn line_end(iter: &mut RIter, len: usize) -> usize {
+    for (token, span) in iter {
+        if *token == Token::LineBreak {
+            return span.end;
+        }
+    }
+
+    len
+}
+
+fn end_line(text: &mut String, newline: &str) {
+    if !text.ends_with('\n') {
+        text.push_str(newline);
+    }
+}
+
+// the whitespace the last line of `prefix` starts with
+fn indentation(prefix: &str) -> &str {
+    let line = prefix.rsplit('\n').next().unwrap_or_default();
+    &line[..line.len() - line.trim_start().len()]
+}
+
+fn segments(path: &[TomlKey]) -> Vec<String> {
+    path.iter().map(ToString::to_string).collect()
+}
+
+fn join(path: &[String]) -> String {
+    path.iter()
+        .map(|segment| format_key(&TomlKey::from(segment.as_str())))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+// the segments of a dotted key, quoted segments may contain dots
+fn parse_path(key: &str) -> Result<Vec<String>, DocumentError> {
+    parse_dotted_key(key).ok_or_else(|| DocumentError::InvalidKey(key.to_string()))
+}
+
+// the value of a key/value as it was written, it was parsed before
+fn parse_repr(repr: &str) -> TomlValue<'static> {
+    let lexed = lex(repr.as_bytes()).expect("values are valid");
+
+    parse_value(RIter::new(&lexed).as_slice())
+        .expect("values are valid")
+        .value
+        .into_owned()
+}
+
+// a key/value of an inline table or a value of an array, as byte ranges of
+// the text they're in. The values of an array have no key
+struct Item {
+    path: Vec<String>,
+    key: Span,
+    value: Span,
+}
+
+// the key/values of the inline table or the values of the array at `span`
+// of `repr`
+fn items(repr: &str, span: Span) -> Vec<Item> {
+    let text = &repr[span.start..span.end];
+    let lexed = lex(text.as_bytes()).expect("values are valid");
+    let mut iter = RIter::new(&lexed);
+    let is_table = iter.next().is_some_and(|(token, _)| *token == Token::Cbo);
+    let mut items = Vec::new();
+
+    loop {
+        while iter.peek().is_some_and(|(token, _)| {
+            token.is_space() || matches!(token, Token::LineBreak | Token::Comma)
+        }) {
+            iter.next();
+        }
+
+        let start = match iter.peek() {
+            Some((Token::Cbc, _)) | Some((Token::Sbc, _)) | None => break,
+            Some(token) => trim_start(token),
+        };
+
+        let mut path = Vec::new();
+        let mut key = Span::new(start, start);
+
+        if is_table {
+            let location = iter.clone();
+            let parsed = parse_key(iter.as_slice(), &[Expected::Token(Token::Eq)])
+                .expect("values are valid");
+            key.end = key_span(location, parsed.section.index.offset - 1).end;
+            path = segments(&parsed.path);
+            iter = parsed.section;
+
+            while iter.peek().is_some_and(|(token, _)| token.is_space()) {
+                iter.next();
+            }
+        }
+
+        let value_start = iter.peek().map_or(text.len(), trim_start);
+        iter = parse_value(iter.as_slice())
+            .expect("values are valid")
+            .section;
+        let value_end = value_start + text[value_start..iter.index.offset].trim_end().len();
+
+        items.push(Item {
+            path,
+            key: Span::new(span.start + key.start, span.start + key.end),
+            value: Span::new(span.start + value_start, span.start + value_end),
+        });
+    }
+
+    items
+}
+
+// the inline table at `span` of `repr`, the last value of an array of them
+fn as_table(repr: &str, span: Span) -> Option<Span> {
+    let text = &repr[span.start..span.end];
+
+    if text.starts_with('[') {
+        let last = items(repr, span).pop()?;
+        return repr[last.value.start..last.value.end]
+            .starts_with('{')
+            .then_some(last.value);
+    }
+
+    text.starts_with('{').then_some(span)
+}
+
+// the innermost inline table of the value `repr` on the way to `path`, along
+// with how many segments of `path` lead to it. `None` if `repr` isn't a table
+fn inline_table(repr: &str, path: &[String]) -> Option<(Span, usize)> {
+    let mut table = as_table(repr, Span::new(0, repr.len()))?;
+    let mut depth = 0;
+
+    loop {
+        let rest = &path[depth..];
+        let inner = items(repr, table).into_iter().find_map(|item| {
+            if item.path.len() < rest.len() && rest.starts_with(&item.path) {
+                as_table(repr, item.value).map(|inner| (inner, item.path.len()))
+            } else {
+                None
+            }
+        });
+
+        match inner {
+            Some((inner, len)) => {
+                table = inner;
+                depth += len;
+            }
+            None => return Some((table, depth)),
+        }
+    }
+}
+
+// the text to cut out of the inline table at `span` to remove the first
+// key/value at or under `path`, a comma next to it included
+fn removed(repr: &str, span: Span, path: &[String]) -> Option<Range<usize>> {
+    let items = items(repr, span);
+    let index = items.iter().position(|item| item.path.starts_with(path))?;
+    let item = &items[index];
+
+    Some(match (index.checked_sub(1), items.get(index + 1)) {
+        (Some(previous), _) => items[previous].value.end..item.value.end,
+        (None, Some(next)) => item.key.start..next.key.start,
+        // the table is left empty, `{}`
+        (None, None) => span.start + 1..span.end - 1,
+    })
+}
+
+// the value at `path` under `value`, going through the last table of arrays
+fn walk<'v>(value: &'v TomlValue<'static>, path: &[String]) -> Option<&'v TomlValue<'static>> {
+    path.iter().try_fold(value, |value, segment| {
+        let table = match value {
+            TomlValue::Table(table) => table,
+            TomlValue::Array(array) => match array.last() {
+                Some(TomlValue::Table(table)) => table,
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        table.get(&TomlKey::from(segment.clone()))
+    })
+}
+
+fn missing(key: &str) -> DocumentError {
+    DocumentError::NotFound(key.to_string())
+}
+
+fn not_a_table(key: &str) -> DocumentError {
+    DocumentError::NotATable(key.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CARGO: &str = r#"# package metadata
+[package]
+name    = "rtoml"   # the crate
+version = "1.2.3"
+flags   = 0x1F
+path    = 'C:\temp'
+authors = [
+    "a",  # first
+    "b",
+]
+
+# the dependencies
+[dependencies]
+serde = { version = "1", features = ["derive"] }
+
+[[bin]]
+name = "one"
+
+[[bin]]
+name = "two"
+# done
+"#;
+
+    fn document(source: &str) -> DocumentMut {
+        DocumentMut::parse(source).unwrap()
+    }
+
+    #[test]
+    fn lossless() {
+        for source in [
+            CARGO,
+            "",
+            "# only a comment",
+            "a = 1",
+            "a = 1 # no line break",
+            "\n\n  a.b  =  \"#\"  \r\n\t[ t . 'u' ]\r\nc = \"\"\"\nx # y\n\"\"\"\n\n",
+            "s = '''\n[not.a.table]\n'''\n",
+            "t = { a = [1, { b = 2 }] }\n[[x]]\n[[x]]\n[x.y]\n",
+        ] {
+            assert_eq!(document(source).to_string(), source);
+        }
+
+        let err = DocumentMut::parse("a = 1\na = 2").unwrap_err();
+        assert!(matches!(err, DocumentError::Parse(_)));
+        assert!(err
+            .to_string()
+            .starts_with("Variable name `a` already used"));
+    }
+
+    #[test]
+    fn get() {
+        let document = document(CARGO);
+
+        assert_eq!(
+            document.get("package.version"),
+            Some(TomlValue::String("1.2.3".into()))
+        );
+        assert_eq!(document.get("package.flags"), Some(TomlValue::Int(31)));
+        assert_eq!(
+            document.get("dependencies.serde.version"),
+            Some(TomlValue::String("1".into()))
+        );
+        assert!(document.get("bin").unwrap().as_array().is_some());
+        // the last table of an array of tables
+        assert_eq!(
+            document.get("bin.name"),
+            Some(TomlValue::String("two".into()))
+        );
+        assert_eq!(document.get("package.missing"), None);
+        assert_eq!(document.get("not a key"), None);
+    }
+
+    #[test]
+    fn set() {
+        let mut document = document(CARGO);
+        document
+            .set("package.version", &TomlValue::String("1.3.0".into()))
+            .unwrap();
+        document
+            .set("dependencies.serde.version", &TomlValue::String("2".into()))
+            .unwrap();
+        document
+            .set("bin.name", &TomlValue::String("three".into()))
+            .unwrap();
+
+        assert_eq!(
+            document.to_string(),
+            CARGO
+                .replace("\"1.2.3\"", "\"1.3.0\"")
+                .replace(
+                    r#"{ version = "1", features = ["derive"] }"#,
+                    r#"{ version = "2", features = ["derive"] }"#
+                )
+                .replace("\"two\"", "\"three\"")
+        );
+        // lookups see the edits
+        assert_eq!(
+            document.get("dependencies.serde.version"),
+            Some(TomlValue::String("2".into()))
+        );
+        assert_eq!(
+            document.to_value(),
+            TomlValue::try_from(document.to_string().as_str()).unwrap()
+        );
+        assert_eq!(
+            document.set("package.missing", &TomlValue::Int(1)),
+            Err(DocumentError::NotFound(String::from("package.missing")))
+        );
+        assert_eq!(
+            document.set("package.", &TomlValue::Int(1)),
+            Err(DocumentError::InvalidKey(String::from("package.")))
+        );
+    }
+
+    #[test]
+    fn insert() {
+        let mut document = document(CARGO);
+        document
+            .insert("package.edition", &TomlValue::String("2018".into()))
+            .unwrap();
+        document
+            .insert("dependencies.serde.optional", &TomlValue::Boolean(true))
+            .unwrap();
+        document
+            .insert("bin.test", &TomlValue::Boolean(false))
+            .unwrap();
+        document.insert("top", &TomlValue::Int(1)).unwrap();
+
+        let expected = CARGO
+            .replace("    \"b\",\n]\n", "    \"b\",\n]\nedition = \"2018\"\n")
+            .replace(
+                r#"features = ["derive"] }"#,
+                r#"features = ["derive"], optional = true }"#,
+            )
+            .replace("name = \"two\"\n", "name = \"two\"\ntest = false\n")
+            .replace("# package metadata\n", "top = 1\n# package metadata\n");
+        assert_eq!(document.to_string(), expected);
+
+        // already there or not in a table
+        assert_eq!(
+            document.insert("top", &TomlValue::Int(2)),
+            Err(DocumentError::KeyExists(String::from("top")))
+        );
+        assert_eq!(
+            document.insert("top.a", &TomlValue::Int(2)),
+            Err(DocumentError::NotATable(String::from("top.a")))
+        );
+
+        let mut document = self::document("[a]\n  b = 1");
+        document.insert("a.c", &TomlValue::Int(2)).unwrap();
+        assert_eq!(document.to_string(), "[a]\n  b = 1\n  c = 2\n");
+
+        let mut document = self::document("a = 1\r\n");
+        document.insert("b", &TomlValue::Int(2)).unwrap();
+        assert_eq!(document.to_string(), "a = 1\r\nb = 2\r\n");
+    }
+
+    #[test]
+    fn remove() {
+        let mut document = document(CARGO);
+
+        assert_eq!(document.remove("package.flags"), Ok(TomlValue::Int(31)));
+        assert_eq!(
+            document.remove("dependencies.serde.features"),
+            Ok(TomlValue::Array(vec![TomlValue::String("derive".into())]))
+        );
+        assert!(document.remove("dependencies").is_ok());
+        assert_eq!(
+            document.remove("dependencies"),
+            Err(DocumentError::NotFound(String::from("dependencies")))
+        );
+        assert_eq!(
+            document.remove("package."),
+            Err(DocumentError::InvalidKey(String::from("package.")))
+        );
+        assert!(!document.contains_key("dependencies.serde"));
+
+        assert_eq!(
+            document.to_string(),
+            r#"# package metadata
+[package]
+name    = "rtoml"   # the crate
+version = "1.2.3"
+path    = 'C:\temp'
+authors = [
+    "a",  # first
+    "b",
+]
+
+[[bin]]
+name = "one"
+
+[[bin]]
+name = "two"
+# done
+"#
+        );
+
+        let mut document = self::document("[a]\nb.c = 1\nb.d = 2\n[a.e]\n[f]\n");
+        document.remove("a").unwrap();
+        assert_eq!(document.to_string(), "[f]\n");
+    }
+
+    #[test]
+    fn rename() {
+        let mut document = document("[a]\nb.c = 1 # c\n[a.d]\n[[e]]\n\"f g\" = { h = 1, i = 2 }\n");
+        document.rename("a", "x").unwrap();
+        document.rename("x.b.c", "y").unwrap();
+        document.rename("e", "z").unwrap();
+        document.rename("z.\"f g\".h", "j").unwrap();
+
+        assert_eq!(
+            document.to_string(),
+            "[x]\nb.y = 1 # c\n[x.d]\n[[z]]\n\"f g\" = { j = 1, i = 2 }\n"
+        );
+
+        // the new name is taken
+        assert_eq!(
+            document.rename("x.d", "b"),
+            Err(DocumentError::KeyExists(String::from("x.b")))
+        );
+        assert_eq!(
+            document.rename("missing", "b"),
+            Err(DocumentError::NotFound(String::from("missing")))
+        );
+        assert_eq!(
+            document.to_string(),
+            "[x]\nb.y = 1 # c\n[x.d]\n[[z]]\n\"f g\" = { j = 1, i = 2 }\n"
+        );
+    }
+
+    #[test]
+    fn inline_literals() {
+        let source = "t = { flags = 0x1F, path = 'C:\\x', list = [ 1,2 ], big = 1_000 }\n";
+        let mut document = document(source);
+
+        document.set("t.flags", &TomlValue::Int(32)).unwrap();
+        assert_eq!(
+            document.to_string(),
+            "t = { flags = 32, path = 'C:\\x', list = [ 1,2 ], big = 1_000 }\n"
+        );
+
+        document.insert("t.u.v", &TomlValue::Int(1)).unwrap();
+        document.rename("t.path", "dir").unwrap();
+        document.remove("t.flags").unwrap();
+        assert_eq!(
+            document.to_string(),
+            "t = { dir = 'C:\\x', list = [ 1,2 ], big = 1_000, u.v = 1 }\n"
+        );
+
+        document.remove("t.list").unwrap();
+        document.remove("t.u").unwrap();
+        assert_eq!(document.to_string(), "t = { dir = 'C:\\x', big = 1_000 }\n");
+
+        // nested inline tables, dotted keys and arrays of inline tables
+        let mut document = self::document("a = [ {b = 0o7}, { c = { d.e = 0b1, d.f = 2 } } ]\n");
+        document.set("a.c.d.f", &TomlValue::Int(3)).unwrap();
+        document.rename("a.c.d", "g").unwrap();
+        document.insert("a.c.h", &TomlValue::Int(4)).unwrap();
+        assert_eq!(
+            document.to_string(),
+            "a = [ {b = 0o7}, { c = { g.e = 0b1, g.f = 3, h = 4 } } ]\n"
+        );
+        assert_eq!(
+            document.insert("a.c.g.e.i", &TomlValue::Int(5)),
+            Err(DocumentError::NotATable(String::from("a.c.g.e.i")))
+        );
+
+        document.remove("a.c.g").unwrap();
+        document.remove("a.c.h").unwrap();
+        document.insert("a.c.i", &TomlValue::Int(5)).unwrap();
+        assert_eq!(
+            document.to_string(),
+            "a = [ {b = 0o7}, { c = { i = 5 } } ]\n"
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let document = document(
+            "# not about a\n\n#   The answer\n#\r\n  # of it all\na = 42 # it is\n\
+             [t] #table\n## b\nb = 1\n[[c]]\n# first\n[[c]]\n# last\n[[c]]\nd = {}\n",
+        );
+        let comments = |leading: &[&str], trailing: Option<&str>| Comments {
+            leading: leading.iter().map(ToString::to_string).collect(),
+            trailing: trailing.map(ToString::to_string),
+        };
+
+        assert_eq!(
+            document.comments_for("a"),
+            Some(comments(&["  The answer", "", "of it all"], Some("it is")))
+        );
+        assert_eq!(
+            document.comments_for("t"),
+            Some(comments(&[], Some("table")))
+        );
+        assert_eq!(document.comments_for("t.b"), Some(comments(&["# b"], None)));
+        assert_eq!(document.comments_for("c"), Some(comments(&["last"], None)));
+        assert!(document.comments_for("c.d").unwrap().is_empty());
+        assert_eq!(document.comments_for("c.d.e"), None);
+        assert_eq!(document.comments_for("e"), None);
+
+        let paths: Vec<_> = document
+            .comments()
+            .into_iter()
+            .map(|(path, _)| path.join("."))
+            .collect();
+        assert_eq!(paths, ["a", "t", "t.b", "c", "c", "c", "c.d"]);
+    }
+
+    #[test]
+    fn format() {
+        let mut document = document(
+            "# top\nzeta = 1\nalpha.beta = [1, 2, 3] # short\n\n[t.u] # u\n  # about b\n  b=2\n\
+             a = [\n  1, # one\n]\n",
+        );
+        document
+            .format(&FormatOptions {
+                indent: 2,
+                indent_tables: true,
+                max_width: 20,
+                align_entries: true,
+                inline_tables: 0,
+                sort_keys: true,
+            })
+            .unwrap();
+
+        assert_eq!(
+            document.to_string(),
+            "# top\nalpha.beta = [\n  1,\n  2,\n  3,\n] # short\nzeta       = 1\n\n  [t.u] # u\n\
+             \x20 a = [\n  1, # one\n]\n  # about b\n  b = 2\n"
+        );
+        assert_eq!(document.get("t.u.b"), Some(TomlValue::Int(2)));
+
+        // the last key/value ends the document without a line break
+        let sort = FormatOptions {
+            sort_keys: true,
+            ..FormatOptions::default()
+        };
+
+        for (source, expected) in [
+            ("b = 1\na = 2", "a = 2\nb = 1\n"),
+            ("b = 1 # x\na = 2 # y", "a = 2 # y\nb = 1 # x\n"),
+            ("# top\n\nb = 1\r\na = 2", "# top\n\na = 2\r\nb = 1\r\n"),
+        ] {
+            let mut document = self::document(source);
+            document.format(&sort).unwrap();
+            assert_eq!(document.to_string(), expected);
+            assert_eq!(document.to_value(), TomlValue::try_from(expected).unwrap());
+        }
+    }
+
+    #[test]
+    fn add_table() {
+        let mut document = document("a = 1\n# end");
+        document.add_table("t").unwrap();
+        document.insert("t.\"b c\"", &TomlValue::Int(2)).unwrap();
+
+        assert_eq!(document.to_string(), "a = 1\n# end\n\n[t]\n\"b c\" = 2\n");
+        assert_eq!(
+            document.add_table("t"),
+            Err(DocumentError::KeyExists(String::from("t")))
+        );
+        assert_eq!(
+            document.add_table("a"),
+            Err(DocumentError::KeyExists(String::from("a")))
+        );
+        // `[a.b]` can't extend the integer `a`
+        let err = document.add_table("a.b").unwrap_err();
+        assert!(matches!(err, DocumentError::Invalid(_)));
+        assert!(err
+            .to_string()
+            .starts_with("Edit makes the document invalid: "));
+        assert_eq!(document.to_string(), "a = 1\n# end\n\n[t]\n\"b c\" = 2\n");
+
+        let mut document = self::document("");
+        document.add_table("t.u").unwrap();
+        assert_eq!(document.to_string(), "[t.u]\n");
+    }
+}
@@ -0,0 +1,494 @@
+use crate::lexer::is_bare_key;
+use crate::{FormatOptions, Table, TomlKey, TomlValue};
+
+use std::fmt::Write;
+
+// writes a table back out as a toml document, key/values of a table come
+// first followed by its sub-tables and then its arrays of tables
+pub struct Emitter {
+    options: FormatOptions,
+    out: String,
+}
+
+impl Emitter {
+    // `pretty` spreads every array over multiple lines
+    pub fn new(pretty: bool) -> Self {
+        Self::with_options(&FormatOptions {
+            max_width: if pretty { 0 } else { usize::MAX },
+            ..FormatOptions::DEFAULT
+        })
+    }
+
+    pub fn with_options(options: &FormatOptions) -> Self {
+        Self {
+            options: *options,
+            out: String::new(),
+        }
+    }
+
+    pub fn emit(mut self, table: &Table) -> String {
+        self.table(&mut Vec::new(), table);
+        self.out
+    }
+
+    // `value` written at `column` of a line indented by `indent`, arrays
+    // that don't fit get a line per value
+    pub fn value_at(mut self, value: &TomlValue, column: usize, indent: &str) -> String {
+        self.wrapped(value, column, indent);
+        self.out
+    }
+
+    fn table(&mut self, path: &mut Vec<String>, table: &Table) {
+        let indent = self.indentation(path.len());
+        let entries = self.entries(table);
+        let inline: Vec<_> = entries
+            .iter()
+            .filter(|(_, value)| self.is_inline(value))
+            .map(|(key, value)| (format_key(key), *value))
+            .collect();
+        let width = if self.options.align_entries {
+            inline.iter().map(|(key, _)| key.chars().count()).max()
+        } else {
+            None
+        };
+
+        for (key, value) in inline {
+            let line = format!("{}{:width$} = ", indent, key, width = width.unwrap_or(0));
+            self.out.push_str(&line);
+            self.wrapped(value, line.chars().count(), &indent);
+            self.out.push('\n');
+        }
+
+        for (key, value) in &entries {
+            if let (TomlValue::Table(sub_table), false) = (value, self.is_inline(value)) {
+                path.push(format_key(key));
+
+                // tables with nothing but sub-tables are created implicitly
+                if sub_table.is_empty() || sub_table.values().any(|value| self.is_inline(value)) {
+                    self.header(path, false);
+                }
+
+                self.table(path, sub_table);
+                path.pop();
+            }
+        }
+
+        for (key, value) in &entries {
+            if let (TomlValue::Array(array), false) = (value, self.is_inline(value)) {
+                path.push(format_key(key));
+
+                for element in array.iter().filter_map(TomlValue::as_table) {
+                    self.header(path, true);
+                    self.table(path, element);
+                }
+
+                path.pop();
+            }
+        }
+    }
+
+    fn header(&mut self, path: &[String], is_array: bool) {
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+
+        let (open, close) = if is_array { ("[[", "]]") } else { ("[", "]") };
+        let indent = self.indentation(path.len());
+
+        let _ = writeln!(self.out, "{}{}{}{}", indent, open, path.join("."), close);
+    }
+
+    // the key/values of `table` in the order they're written in
+    fn entries<'t, 'a>(&self, table: &'t Table<'a>) -> Vec<(&'t TomlKey<'a>, &'t TomlValue<'a>)> {
+        let mut entries: Vec<_> = table.iter().collect();
+
+        if self.options.sort_keys {
+            entries.sort_by_cached_key(|(key, _)| key.to_string());
+        }
+
+        entries
+    }
+
+    // indentation of a table `depth` keys deep, along with its key/values
+    fn indentation(&self, depth: usize) -> String {
+        if self.options.indent_tables {
+            " ".repeat(depth.saturating_sub(1) * self.options.indent)
+        } else {
+            String::new()
+        }
+    }
+
+    // values that are written on the same line as their key, everything but
+    // arrays made only of tables and tables too big to be inline
+    fn is_inline(&self, value: &TomlValue) -> bool {
+        match value {
+            TomlValue::Table(table) => {
+                table.len() <= self.options.inline_tables
+                    && self.options.inline_tables > 0
+                    && !table.values().any(|value| value.as_table().is_some())
+            }
+            TomlValue::Array(array) => {
+                array.is_empty() || !array.iter().all(|value| value.as_table().is_some())
+            }
+            _ => true,
+        }
+    }
+
+    fn wrapped(&mut self, value: &TomlValue, column: usize, indent: &str) {
+        let start = self.out.len();
+        self.value(value);
+
+        let array = match value {
+            TomlValue::Array(array) if !array.is_empty() => array,
+            _ => return,
+        };
+
+        if column + self.out[start..].chars().count() <= self.options.max_width {
+            return;
+        }
+
+        self.out.truncate(start);
+        self.out.push_str("[\n");
+
+        for value in array {
+            self.out.push_str(indent);
+            self.out.push_str(&" ".repeat(self.options.indent));
+            self.value(value);
+            self.out.push_str(",\n");
+        }
+
+        self.out.push_str(indent);
+        self.out.push(']');
+    }
+
+    fn value(&mut self, value: &TomlValue) {
+        match value {
+            TomlValue::Int(x) => {
+                let _ = write!(self.out, "{}", x);
+            }
+            TomlValue::Float(x) => self.out.push_str(&format_float(*x)),
+            TomlValue::String(x) => self.out.push_str(&format_string(x)),
+            TomlValue::Boolean(x) => {
+                let _ = write!(self.out, "{}", x);
+            }
+            TomlValue::DateTime(x) => {
+                let _ = write!(self.out, "{}", x);
+            }
+            TomlValue::Array(x) => {
+                self.out.push('[');
+                for (index, value) in x.iter().enumerate() {
+                    if index > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.value(value);
+                }
+                self.out.push(']');
+            }
+            TomlValue::Table(x) => {
+                self.out.push('{');
+                for (index, (key, value)) in self.entries(x).into_iter().enumerate() {
+                    self.out.push_str(if index > 0 { ", " } else { " " });
+                    self.out.push_str(&format_key(key));
+                    self.out.push_str(" = ");
+                    self.value(value);
+                }
+                self.out.push_str(if x.is_empty() { "}" } else { " }" });
+            }
+        }
+    }
+}
+
+// a value as it's written after the `=` of a key/value
+pub fn format_value(value: &TomlValue) -> String {
+    let mut emitter = Emitter::new(false);
+    emitter.value(value);
+    emitter.out
+}
+
+pub fn format_key(key: &TomlKey) -> String {
+    let key = key.to_string();
+
+    if is_bare_key(&key) {
+        key
+    } else {
+        format_string(&key)
+    }
+}
+
+pub fn format_float(float: f64) -> String {
+    if float.is_nan() {
+        String::from("nan")
+    } else if float.is_infinite() {
+        String::from(if float > 0.0 { "inf" } else { "-inf" })
+    } else {
+        // debug formatting always keeps a `.0` or an exponent
+        format!("{:?}", float)
+    }
+}
+
+// https://toml.io/en/v1.0.0#string
+pub fn format_string(string: &str) -> String {
+    let mut out = String::with_capacity(string.len() + 2);
+    out.push('"');
+
+    for char in string.chars() {
+        match char {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\x08' => out.push_str("\\b"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\x0C' => out.push_str("\\f"),
+            '\r' => out.push_str("\\r"),
+            '\x00'..='\x1F' | '\x7F' => {
+                let _ = write!(out, "\\u{:04X}", char as u32);
+            }
+            _ => out.push(char),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    #[test]
+    fn strings() {
+        assert_eq!(format_string("a\"b\\c"), r#""a\"b\\c""#);
+        assert_eq!(format_string("tab\tnew\nline"), r#""tab\tnew\nline""#);
+        assert_eq!(format_string("\x01\x7F"), r#""\u0001\u007F""#);
+    }
+
+    #[test]
+    fn keys_and_floats() {
+        assert_eq!(format_key(&TomlKey::from("bare-key_1")), "bare-key_1");
+        assert_eq!(format_key(&TomlKey::from("127.0.0.1")), r#""127.0.0.1""#);
+        assert_eq!(format_key(&TomlKey::from("")), r#""""#);
+        assert_eq!(format_float(1.0), "1.0");
+        assert_eq!(format_float(-0.0), "-0.0");
+        assert_eq!(format_float(f64::NEG_INFINITY), "-inf");
+        assert_eq!(format_float(f64::NAN), "nan");
+    }
+
+    #[test]
+    fn sections() {
+        let toml = r#"
+title = "a"
+
+[server.http]
+port = 80
+
+[[server.routes]]
+path = "/"
+
+[[server.routes]]
+path = "/api"
+"#;
+        let value = TomlValue::try_from(toml).unwrap();
+        let emitted = Emitter::new(false).emit(value.as_table().unwrap());
+
+        assert!(emitted.starts_with("title = \"a\"\n"));
+        assert!(emitted.contains("[server.http]\nport = 80\n"));
+        assert!(emitted.contains("[[server.routes]]\npath = \"/\"\n"));
+        assert!(!emitted.contains("[server]\n"));
+        assert_eq!(TomlValue::try_from(emitted.as_str()).unwrap(), value);
+        // keys come out in the order they were defined
+        assert_eq!(emitted, toml.trim_start());
+    }
+
+    #[test]
+    fn format_options() {
+        let toml = r#"
+name = "rtoml"
+version = "0.1.1"
+keywords = ["toml", "parser", "serializer", "format", "preserving", "editing", "document"]
+
+[dependencies]
+serde = { version = "1", optional = true }
+indexmap = "2"
+
+[dependencies.speedate.nested]
+a = 1
+"#;
+        let value = TomlValue::try_from(toml).unwrap();
+        let options = FormatOptions {
+            indent: 2,
+            indent_tables: true,
+            max_width: 40,
+            align_entries: true,
+            inline_tables: 2,
+            sort_keys: true,
+        };
+        let formatted = value.to_toml_string_with(&options);
+
+        assert_eq!(
+            formatted,
+            r#"keywords = [
+  "toml",
+  "parser",
+  "serializer",
+  "format",
+  "preserving",
+  "editing",
+  "document",
+]
+name     = "rtoml"
+version  = "0.1.1"
+
+[dependencies]
+indexmap = "2"
+serde    = { optional = true, version = "1" }
+
+  [dependencies.speedate]
+  nested = { a = 1 }
+"#
+        );
+        assert_eq!(TomlValue::try_from(formatted.as_str()).unwrap(), value);
+
+        // the defaults only wrap long arrays
+        let formatted = value.to_toml_string_with(&FormatOptions::default());
+        assert!(formatted
+            .starts_with("name = \"rtoml\"\nversion = \"0.1.1\"\nkeywords = [\n    \"toml\",\n"));
+        assert!(formatted.contains("\n[dependencies.serde]\nversion = \"1\"\n"));
+    }
+
+    mod properties {
+        use crate::fuzz::{round_trip, same};
+        use crate::{DateTime, FormatOptions, Table, TomlKey, TomlValue};
+
+        use std::convert::TryFrom;
+
+        use proptest::prelude::*;
+        use proptest::test_runner::RngSeed;
+
+        // the same cases on every run, without writing failures to disk
+        fn config() -> ProptestConfig {
+            ProptestConfig {
+                cases: 512,
+                rng_seed: RngSeed::Fixed(0x7012_1A11),
+                failure_persistence: None,
+                ..ProptestConfig::default()
+            }
+        }
+
+        fn key() -> impl Strategy<Value = TomlKey<'static>> {
+            prop_oneof!["[A-Za-z0-9_-]{1,8}", any::<String>()].prop_map(TomlKey::from)
+        }
+
+        fn date_time() -> impl Strategy<Value = TomlValue<'static>> {
+            let date = (0..10_000u32, 1..=12u32, 1..=28u32)
+                .prop_map(|(y, m, d)| format!("{:04}-{:02}-{:02}", y, m, d));
+            let time = (
+                0..24u32,
+                0..60u32,
+                0..60u32,
+                prop::option::of(0..1_000_000u32),
+            )
+                .prop_map(|(h, m, s, fraction)| match fraction {
+                    Some(fraction) => format!("{:02}:{:02}:{:02}.{:06}", h, m, s, fraction),
+                    None => format!("{:02}:{:02}:{:02}", h, m, s),
+                });
+            let offset = prop_oneof![
+                Just(String::new()),
+                Just(String::from("Z")),
+                (-23..24i32, 0..60u32).prop_map(|(h, m)| format!("{:+03}:{:02}", h, m)),
+            ];
+
+            prop_oneof![
+                date.clone(),
+                time.clone(),
+                (date, time, offset).prop_map(|(date, time, offset)| date + "T" + &time + &offset),
+            ]
+            .prop_map(|string| TomlValue::DateTime(string.parse::<DateTime>().unwrap()))
+        }
+
+        fn value() -> impl Strategy<Value = TomlValue<'static>> {
+            let leaf = prop_oneof![
+                any::<i64>().prop_map(TomlValue::Int),
+                any::<f64>().prop_map(TomlValue::Float),
+                any::<String>().prop_map(TomlValue::String),
+                any::<bool>().prop_map(TomlValue::Boolean),
+                date_time(),
+            ];
+
+            leaf.prop_recursive(4, 64, 6, |value| {
+                prop_oneof![
+                    prop::collection::vec(value.clone(), 0..6).prop_map(TomlValue::Array),
+                    table(value).prop_map(TomlValue::Table),
+                ]
+            })
+        }
+
+        fn table(
+            value: impl Strategy<Value = TomlValue<'static>>,
+        ) -> impl Strategy<Value = Table<'static>> {
+            prop::collection::vec((key(), value), 0..6)
+                .prop_map(|entries| entries.into_iter().collect())
+        }
+
+        fn format_options() -> impl Strategy<Value = FormatOptions> {
+            (
+                0..8usize,
+                any::<bool>(),
+                0..100usize,
+                any::<bool>(),
+                0..4usize,
+                any::<bool>(),
+            )
+                .prop_map(
+                    |(
+                        indent,
+                        indent_tables,
+                        max_width,
+                        align_entries,
+                        inline_tables,
+                        sort_keys,
+                    )| {
+                        FormatOptions {
+                            indent,
+                            indent_tables,
+                            max_width,
+                            align_entries,
+                            inline_tables,
+                            sort_keys,
+                        }
+                    },
+                )
+        }
+
+        proptest! {
+            #![proptest_config(config())]
+
+            #[test]
+            fn formatted_documents_parse_to_the_same_value(
+                document in table(value()),
+                options in format_options(),
+            ) {
+                let value = TomlValue::Table(document);
+                let formatted = value.to_toml_string_with(&options);
+                let parsed = TomlValue::try_from(formatted.as_str())
+                    .unwrap_or_else(|err| panic!("{} in\n{}", err, formatted));
+
+                prop_assert!(same(&value, &parsed), "{:?} became {:?}", value, parsed);
+            }
+
+            #[test]
+            fn emitted_documents_parse_to_the_same_value(document in table(value())) {
+                round_trip(&TomlValue::Table(document));
+            }
+
+            // made of the characters toml gives a meaning to, so most inputs
+            // get past the lexer
+            #[test]
+            fn any_input(source in "[a-z0-9 \t\n.,=+_:'\"#\\[\\]{}\\\\-]{0,64}") {
+                crate::fuzz::lex(source.as_bytes());
+                crate::fuzz::parse(source.as_bytes());
+            }
+        }
+    }
+}
@@ -1,83 +1,833 @@
 use crate::lexer::Token;
 use crate::parser::r_iter::RIter;
-use crate::parser::r_slice::RIndex;
+use crate::{IntoOwned, TomlValue};
 
 use std::error::Error;
 use std::fmt;
-use std::fmt::Display;
+use std::fmt::{Display, Write};
+use std::iter;
 
-use simdutf8::basic::Utf8Error;
+pub use crate::lexer::Span;
+pub use crate::parser::r_slice::RIndex;
 
 #[derive(Debug)]
 pub struct ErrLocation<'a> {
     index: RIndex,
+    span: Span,
     token: Token<'a>,
 }
 
 impl<'a> ErrLocation<'a> {
-    pub fn new(mut iter: RIter<'a>) -> Self {
+    // the next token of `iter` is the one the error is about, at the end of
+    // the input the span is empty
+    pub fn new(mut iter: RIter<'_, 'a>) -> Self {
+        let index = iter.index;
         let mut token = Token::Literal("");
-        if let Some((x, _)) = iter.next() {
+        let mut span = Span::new(index.offset, index.offset);
+        if let Some((x, x_span)) = iter.next() {
             token = *x;
+            span = *x_span;
         }
         Self {
-            index: iter.index,
+            index: index.at(span.start),
+            span,
             token,
         }
     }
+
+    // a position found without going through the tokens
+    pub(crate) fn at(index: RIndex) -> Self {
+        Self {
+            index,
+            span: Span::new(index.offset, index.offset),
+            token: Token::Literal(""),
+        }
+    }
+
+    // point at `span` rather than the token, a key spread over several tokens
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.index = self.index.at(span.start);
+        self.span = span;
+        self
+    }
+
+    // what the message shows instead of the token the error was found at
+    pub(crate) fn with_token(mut self, token: Token<'a>) -> Self {
+        self.token = token;
+        self
+    }
+}
+
+// what the parser was looking for when it found an unexpected token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expected {
+    Token(Token<'static>),
+    Key,
+    Value,
+}
+
+impl Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Expected::Token(Token::LineBreak) => f.write_str("a new line"),
+            Expected::Token(Token::Literal(x)) => write!(f, "`{}`", x),
+            Expected::Token(token) => write!(f, "`{}`", char::from(*token)),
+            Expected::Key => f.write_str("a key"),
+            Expected::Value => f.write_str("a value"),
+        }
+    }
+}
+
+// `a`, `a or b`, `a, b or c`
+fn one_of(expected: &[Expected]) -> String {
+    let mut string = String::new();
+
+    for (index, item) in expected.iter().enumerate() {
+        if index > 0 {
+            string.push_str(if index + 1 == expected.len() {
+                " or "
+            } else {
+                ", "
+            });
+        }
+        let _ = write!(string, "{}", item);
+    }
+
+    string
+}
+
+// an escape sequence as written, `token` is what follows the backslash
+fn escape_sequence(token: &Token) -> String {
+    match token {
+        Token::LineBreak => String::from("`\\` followed by a new line"),
+        Token::Literal(x) => format!("`\\{}`", x),
+        token => format!("`\\{}`", char::from(*token)),
+    }
+}
+
+// the token an error was found at, as written in messages
+fn found(token: &Token) -> String {
+    match token {
+        Token::Literal("") => String::from("end of input"),
+        Token::Literal(x) if x.trim().is_empty() => String::from("whitespace"),
+        Token::Literal(x) => format!("`{}`", x.trim()),
+        Token::LineBreak => String::from("a new line"),
+        token => format!("`{}`", char::from(*token)),
+    }
 }
 
 #[derive(Debug)]
 pub enum TomlError<'a> {
     UnknownEscapeSequence(ErrLocation<'a>),
-    NameUsed(ErrLocation<'a>),
+    // the second span is the first definition of the name, when known
+    NameUsed(ErrLocation<'a>, Option<Span>),
     CannotParseValue(ErrLocation<'a>),
     UnspecifiedValue(ErrLocation<'a>),
+    Utf8Error(ErrLocation<'a>),
+    // control characters other than tab aren't allowed anywhere, not even in
+    // comments, and a carriage return must be followed by a line feed
+    ControlCharacter(ErrLocation<'a>),
+    UnexpectedCharacter(ErrLocation<'a>, &'static [Expected]),
+    // the document went over one of its `ParseOptions`
+    LimitExceeded(ErrLocation<'a>, Limit),
+}
+
+// one of the `ParseOptions` limits along with its value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Limit {
+    Depth(usize),
+    Size(usize),
+    StringLength(usize),
+    Keys(usize),
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Limit::Depth(x) => write!(f, "{} nested arrays and inline tables", x),
+            Limit::Size(x) => write!(f, "{} bytes per document", x),
+            Limit::StringLength(x) => write!(f, "{} bytes per string", x),
+            Limit::Keys(x) => write!(f, "{} keys", x),
+        }
+    }
+}
+
+// what went wrong, shared by the borrowed and owned errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    UnknownEscapeSequence,
+    NameUsed,
+    CannotParseValue,
+    UnspecifiedValue,
     Utf8Error,
-    UnexpectedCharacter(ErrLocation<'a>, &'a [Token<'a>]),
+    UnexpectedCharacter,
+    ControlCharacter,
+    LimitExceeded,
 }
 
-impl Display for TomlError<'_> {
+impl ErrorKind {
+    // stable identifier of the kind for tools matching on errors, codes are
+    // never reused once assigned, E0007 was the retired `custom` kind
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::Utf8Error => "E0001",
+            ErrorKind::UnexpectedCharacter => "E0002",
+            ErrorKind::NameUsed => "E0003",
+            ErrorKind::UnknownEscapeSequence => "E0004",
+            ErrorKind::CannotParseValue => "E0005",
+            ErrorKind::UnspecifiedValue => "E0006",
+            ErrorKind::ControlCharacter => "E0008",
+            ErrorKind::LimitExceeded => "E0009",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ErrorKind::Utf8Error => "invalid utf-8",
+            ErrorKind::UnexpectedCharacter => "unexpected character",
+            ErrorKind::NameUsed => "duplicate key",
+            ErrorKind::UnknownEscapeSequence => "unknown escape sequence",
+            ErrorKind::CannotParseValue => "invalid value",
+            ErrorKind::UnspecifiedValue => "missing value",
+            ErrorKind::ControlCharacter => "control character",
+            ErrorKind::LimitExceeded => "limit exceeded",
+        }
+    }
+}
+
+impl Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{} {}", self.code(), self.description())
+    }
+}
+
+impl TomlError<'_> {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UnknownEscapeSequence(_) => ErrorKind::UnknownEscapeSequence,
+            Self::NameUsed(..) => ErrorKind::NameUsed,
+            Self::CannotParseValue(_) => ErrorKind::CannotParseValue,
+            Self::UnspecifiedValue(_) => ErrorKind::UnspecifiedValue,
+            Self::Utf8Error(_) => ErrorKind::Utf8Error,
+            Self::ControlCharacter(_) => ErrorKind::ControlCharacter,
+            Self::UnexpectedCharacter(..) => ErrorKind::UnexpectedCharacter,
+            Self::LimitExceeded(..) => ErrorKind::LimitExceeded,
+        }
+    }
+
+    // the message without its location, for callers that show where the
+    // error is themselves
+    pub fn message(&self) -> String {
         match self {
             Self::UnknownEscapeSequence(err) => {
-                write!(f, "Unknown escape sequence {} at {}", err.token, err.index)
+                format!("Unknown escape sequence {}", escape_sequence(&err.token))
             }
-            Self::NameUsed(err) => {
-                write!(
-                    f,
-                    "Variable name {} already used at {}",
-                    err.token, err.index
-                )
+            Self::NameUsed(err, _) => {
+                format!("Variable name {} already used", found(&err.token))
             }
-            Self::CannotParseValue(err) => {
-                write!(f, "Cannot parse value {} at {}", err.token, err.index)
+            Self::CannotParseValue(err) => format!("Cannot parse value {}", found(&err.token)),
+            Self::UnspecifiedValue(err) => format!("Expected a value, found {}", found(&err.token)),
+            Self::Utf8Error(_) => String::from("Invalid UTF8 bytes while lexing"),
+            Self::ControlCharacter(_) => String::from("Invalid control character while lexing"),
+            Self::UnexpectedCharacter(loc, expected) => format!(
+                "Unexpected {}, expected {}",
+                found(&loc.token),
+                one_of(expected)
+            ),
+            Self::LimitExceeded(_, limit) => format!("Exceeded the limit of {}", limit),
+        }
+    }
+
+    // tokens that would have been accepted instead of the unexpected one
+    pub fn expected(&self) -> Vec<String> {
+        match self {
+            Self::UnexpectedCharacter(_, expected) => {
+                expected.iter().map(ToString::to_string).collect()
             }
-            Self::UnspecifiedValue(err) => {
-                write!(f, "Unspecified value {} at {}", err.token, err.index)
+            _ => Vec::new(),
+        }
+    }
+
+    // where in the input the error was found, errors that didn't come from
+    // the parser have no location
+    pub fn index(&self) -> Option<RIndex> {
+        match self {
+            Self::UnknownEscapeSequence(loc)
+            | Self::NameUsed(loc, _)
+            | Self::CannotParseValue(loc)
+            | Self::UnspecifiedValue(loc)
+            | Self::Utf8Error(loc)
+            | Self::ControlCharacter(loc)
+            | Self::UnexpectedCharacter(loc, _)
+            | Self::LimitExceeded(loc, _) => Some(loc.index),
+        }
+    }
+
+    // byte range of the input the error is about
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::UnknownEscapeSequence(loc)
+            | Self::NameUsed(loc, _)
+            | Self::CannotParseValue(loc)
+            | Self::UnspecifiedValue(loc)
+            | Self::Utf8Error(loc)
+            | Self::ControlCharacter(loc)
+            | Self::UnexpectedCharacter(loc, _)
+            | Self::LimitExceeded(loc, _) => Some(loc.span),
+        }
+    }
+
+    // the error message followed by the line of `source` it was found on,
+    // with the offending part underlined, for `a = 1\n[b]\n[c d]`
+    //
+    // error[E0002]: Unexpected `c d`, expected `]` at line: 3, column: 2
+    //  --> 3:2
+    //   |
+    // 3 | [c d]
+    //   |  ^^^
+    pub fn render(&self, source: &str) -> String {
+        render(
+            "error",
+            self.kind().code(),
+            self,
+            self.span(),
+            self.original(),
+            source,
+        )
+    }
+
+    // where a duplicated key or table was first defined
+    pub fn original(&self) -> Option<Span> {
+        match self {
+            Self::NameUsed(_, original) => *original,
+            _ => None,
+        }
+    }
+
+    // the limit a document went over
+    pub fn limit(&self) -> Option<Limit> {
+        match self {
+            Self::LimitExceeded(_, limit) => Some(*limit),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn render(
+    severity: &str,
+    code: &str,
+    message: &dyn Display,
+    span: Option<Span>,
+    original: Option<Span>,
+    source: &str,
+) -> String {
+    let mut out = format!("{}[{}]: {}\n", severity, code, message);
+    let index = LineIndex::new(source);
+
+    if let Some(span) = span {
+        snippet(&mut out, &index, span);
+    }
+
+    if let Some(original) = original {
+        out.push_str("note: first defined here\n");
+        snippet(&mut out, &index, original);
+    }
+
+    out
+}
+
+// write the line holding the start of `span`, underlining the part of the
+// span that is on that line, columns are counted in characters
+fn snippet(out: &mut String, index: &LineIndex, span: Span) {
+    let position = index.position(span.start);
+    let line_start = index.starts[position.line];
+    let start = line_start + position.column;
+    let line = index.line(position.line).unwrap_or_default();
+
+    let end = floor_char_boundary(index.source, span.end.clamp(start, line_start + line.len()));
+    let line_number = position.line + 1;
+    let column = line[..position.column].chars().count() + 1;
+    let width = index.source[start..end].chars().count().max(1);
+    let gutter = " ".repeat(line_number.to_string().len());
+
+    let _ = writeln!(out, "{}--> {}:{}", gutter, line_number, column);
+    let _ = writeln!(out, "{} |", gutter);
+    let _ = writeln!(out, "{} | {}", line_number, line);
+    let _ = writeln!(
+        out,
+        "{} | {}{}",
+        gutter,
+        " ".repeat(column - 1),
+        "^".repeat(width)
+    );
+}
+
+// the byte offset every line of a document starts at, turns the byte offsets
+// of spans into lines and columns
+#[derive(Debug, Clone)]
+pub struct LineIndex<'s> {
+    source: &'s str,
+    starts: Vec<usize>,
+}
+
+// a line and a column, both starting at 0, the column is counted in bytes
+// and in the utf-16 code units editors use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub utf16_column: usize,
+}
+
+impl<'s> LineIndex<'s> {
+    pub fn new(source: &'s str) -> Self {
+        let starts = iter::once(0)
+            .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+
+        Self { source, starts }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.starts.len()
+    }
+
+    // offsets past the end are clamped to it, offsets inside of a character
+    // are moved to its start
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = floor_char_boundary(self.source, offset);
+        // the first line starts at 0 so there is always one
+        let line = self.starts.partition_point(|start| *start <= offset) - 1;
+        let column = &self.source[self.starts[line]..offset];
+
+        Position {
+            line,
+            column: column.len(),
+            utf16_column: column.encode_utf16().count(),
+        }
+    }
+
+    // content of a line without its line ending
+    pub fn line(&self, line: usize) -> Option<&'s str> {
+        let start = *self.starts.get(line)?;
+        let end = self
+            .starts
+            .get(line + 1)
+            .map_or(self.source.len(), |end| end - 1);
+
+        Some(self.source[start..end].trim_end_matches('\r'))
+    }
+}
+
+fn floor_char_boundary(source: &str, index: usize) -> usize {
+    let mut index = index.min(source.len());
+    while !source.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+impl Display for TomlError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.write_str(&self.message())?;
+
+        match self.index() {
+            Some(index) => write!(f, " at {}", index),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Error for TomlError<'_> {}
+
+// a `TomlError` that no longer borrows from the input, so it can outlive the
+// parsed buffer and be sent across threads or boxed into a `dyn Error`
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedTomlError {
+    message: String,
+    kind: ErrorKind,
+    index: Option<RIndex>,
+    span: Option<Span>,
+    original: Option<Span>,
+    expected: Vec<String>,
+}
+
+impl OwnedTomlError {
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.kind.code()
+    }
+
+    pub fn index(&self) -> Option<RIndex> {
+        self.index
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    pub fn expected(&self) -> &[String] {
+        &self.expected
+    }
+
+    pub fn original(&self) -> Option<Span> {
+        self.original
+    }
+
+    // same as `TomlError::render`
+    pub fn render(&self, source: &str) -> String {
+        render(
+            "error",
+            self.kind.code(),
+            self,
+            self.span,
+            self.original,
+            source,
+        )
+    }
+}
+
+impl From<TomlError<'_>> for OwnedTomlError {
+    fn from(err: TomlError<'_>) -> Self {
+        Self {
+            message: err.to_string(),
+            kind: err.kind(),
+            index: err.index(),
+            span: err.span(),
+            original: err.original(),
+            expected: err.expected(),
+        }
+    }
+}
+
+impl IntoOwned for TomlError<'_> {
+    type Owned = OwnedTomlError;
+
+    fn into_owned(self) -> Self::Owned {
+        OwnedTomlError::from(self)
+    }
+}
+
+impl Display for OwnedTomlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for OwnedTomlError {}
+
+// raised while converting a `TomlValue` into a typed value, the path points
+// at the field that couldn't be converted
+#[derive(Debug, Clone, PartialEq)]
+pub struct FromTomlError {
+    path: Vec<Segment>,
+    kind: FromTomlErrorKind,
+}
+
+// a step of the path to a value, the key in a table or the index in an array
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FromTomlErrorKind {
+    Mismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    OutOfRange(&'static str),
+    Missing,
+}
+
+impl FromTomlError {
+    pub fn mismatch(expected: &'static str, found: &TomlValue) -> Self {
+        Self::from(FromTomlErrorKind::Mismatch {
+            expected,
+            found: found.type_name(),
+        })
+    }
+
+    pub fn missing() -> Self {
+        Self::from(FromTomlErrorKind::Missing)
+    }
+
+    // the error happened in the value of `key`, errors are built from the
+    // innermost value outwards so segments are prepended
+    pub fn at(mut self, key: &str) -> Self {
+        self.path.insert(0, Segment::Key(key.to_string()));
+        self
+    }
+
+    pub fn at_index(mut self, index: usize) -> Self {
+        self.path.insert(0, Segment::Index(index));
+        self
+    }
+
+    pub fn kind(&self) -> &FromTomlErrorKind {
+        &self.kind
+    }
+
+    // dotted path of the field, `servers[1].port`
+    pub fn path(&self) -> String {
+        let mut path = String::new();
+
+        for segment in self.path.iter() {
+            match segment {
+                Segment::Key(key) => {
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(key);
+                }
+                Segment::Index(index) => {
+                    let _ = write!(path, "[{}]", index);
+                }
             }
-            Self::Utf8Error => {
-                write!(f, "Invalid UTF8 bytes while lexing")
+        }
+
+        path
+    }
+}
+
+impl From<FromTomlErrorKind> for FromTomlError {
+    fn from(kind: FromTomlErrorKind) -> Self {
+        Self {
+            path: Vec::new(),
+            kind,
+        }
+    }
+}
+
+impl Display for FromTomlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path())?;
+        }
+
+        match &self.kind {
+            FromTomlErrorKind::Mismatch { expected, found } => {
+                write!(f, "expected {}, found {}", expected, found)
             }
-            Self::UnexpectedCharacter(loc, expected) => {
-                let mut string = String::new();
-                for items in expected.iter() {
-                    string.push_str(&items.to_string())
-                }
-                write!(
-                    f,
-                    "Unexpected character {}, expected {} at {}",
-                    loc.token, string, loc.index
-                )
+            FromTomlErrorKind::OutOfRange(ty) => write!(f, "value out of range for {}", ty),
+            FromTomlErrorKind::Missing => f.write_str("missing field"),
+        }
+    }
+}
+
+impl Error for FromTomlError {}
+
+// an edit of a `DocumentMut` that couldn't be made, the document is left as
+// it was
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocumentError {
+    // the source given to `DocumentMut::parse` isn't a valid document
+    Parse(Box<OwnedTomlError>),
+    // the string given isn't a dotted key
+    InvalidKey(String),
+    KeyExists(String),
+    NotFound(String),
+    // the key is under a value that isn't a table
+    NotATable(String),
+    // the edited document doesn't parse, like a header naming a table that
+    // a dotted key already defined
+    Invalid(Box<OwnedTomlError>),
+}
+
+impl Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            DocumentError::Parse(err) => Display::fmt(err, f),
+            DocumentError::InvalidKey(key) => write!(f, "`{}` is not a key", key),
+            DocumentError::KeyExists(key) => write!(f, "Key `{}` already exists", key),
+            DocumentError::NotFound(key) => write!(f, "Key `{}` doesn't exist", key),
+            DocumentError::NotATable(key) => {
+                write!(f, "Key `{}` is under a value that isn't a table", key)
             }
+            DocumentError::Invalid(err) => write!(f, "Edit makes the document invalid: {}", err),
+        }
+    }
+}
+
+impl Error for DocumentError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DocumentError::Parse(err) | DocumentError::Invalid(err) => Some(err.as_ref()),
+            _ => None,
         }
     }
 }
 
-impl From<Utf8Error> for TomlError<'_> {
-    fn from(_: Utf8Error) -> Self {
-        TomlError::Utf8Error
+impl From<OwnedTomlError> for DocumentError {
+    fn from(err: OwnedTomlError) -> Self {
+        DocumentError::Invalid(Box::new(err))
     }
 }
 
-impl Error for TomlError<'_> {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    #[test]
+    fn render_snippet() {
+        let source = "a = 1\r\nb = 2\r\n[c d]\r\n";
+        let err = TomlValue::try_from(source).unwrap_err();
+
+        assert_eq!(err.span(), Some(Span::new(15, 18)));
+        assert!(err.render(source).ends_with(
+            " --> 3:2
+  |
+3 | [c d]
+  |  ^^^
+"
+        ));
+
+        let source = "a = \"\\u00e9\" \n\n\n\n\n\n\n\n\n[é é]";
+        let err = TomlValue::try_from(source).unwrap_err();
+        assert!(err.render(source).ends_with(
+            "  --> 10:2
+   |
+10 | [é é]
+   |  ^^^
+"
+        ));
+    }
+
+    #[test]
+    fn owned_error() {
+        fn parse(source: String) -> Result<TomlValue<'static>, Box<dyn Error + Send + Sync>> {
+            let value = TomlValue::try_from(source.as_str()).map_err(OwnedTomlError::from)?;
+            Ok(value.into_owned())
+        }
+
+        let err = parse(String::from("a = 1\n[b c]")).unwrap_err();
+        let err = err.downcast_ref::<OwnedTomlError>().unwrap();
+
+        assert_eq!(err.kind(), ErrorKind::UnexpectedCharacter);
+        assert_eq!(err.span(), Some(Span::new(7, 10)));
+        assert_eq!(err.expected(), ["`]`"]);
+        assert_eq!(err.index().map(|index| index.line), Some(1));
+        assert!(err
+            .render("a = 1\n[b c]")
+            .ends_with("2 | [b c]\n  |  ^^^\n"));
+
+        let err = TomlValue::try_from("a = \"\\x\"").unwrap_err();
+        let message = err.to_string();
+        let owned = err.into_owned();
+        assert_eq!(owned.message(), message);
+        assert_eq!(owned.kind(), ErrorKind::UnknownEscapeSequence);
+        assert!(owned.expected().is_empty());
+    }
+
+    #[test]
+    fn line_index() {
+        let source = "a = \"é😀\"\r\nb = 1\n\nc";
+        let index = LineIndex::new(source);
+
+        assert_eq!(index.line_count(), 4);
+        assert_eq!(index.line(0), Some("a = \"é😀\""));
+        assert_eq!(index.line(2), Some(""));
+        assert_eq!(index.line(4), None);
+        // the closing quote, after 2 bytes of `é` and 4 of the emoji
+        assert_eq!(
+            index.position(11),
+            Position {
+                line: 0,
+                column: 11,
+                utf16_column: 8
+            }
+        );
+        // inside of the emoji
+        assert_eq!(index.position(9).column, 7);
+        assert_eq!(index.position(14).line, 1);
+        assert_eq!(index.position(source.len()).line, 3);
+        assert_eq!(index.position(usize::MAX).column, 1);
+    }
+
+    #[test]
+    fn token_position() {
+        let source = "# é comment\na = 1 # b\nb = [1,\n  \"😀\", @]";
+        let err = TomlValue::try_from(source).unwrap_err();
+        let index = err.index().unwrap();
+
+        assert_eq!((index.line, index.col, index.offset), (3, 10, 41));
+        assert_eq!(err.span().map(|span| span.start), Some(41));
+        assert!(err.to_string().ends_with("at line: 4, column: 11"));
+        assert_eq!(LineIndex::new(source).position(41).utf16_column, 8);
+    }
+
+    #[test]
+    fn end_of_input() {
+        let source = "[a";
+        let err = TomlValue::try_from(source).unwrap_err();
+
+        assert_eq!(err.span(), Some(Span::new(2, 2)));
+        assert!(err.render(source).ends_with("1 | [a\n  |   ^\n"));
+    }
+
+    #[test]
+    fn render_original() {
+        let source = "[a]\nb = 1\n\n[a]\n";
+        let err = TomlValue::try_from(source).unwrap_err();
+
+        assert!(err.render(source).ends_with(
+            " --> 4:2
+  |
+4 | [a]
+  |  ^
+note: first defined here
+ --> 1:2
+  |
+1 | [a]
+  |  ^
+"
+        ));
+        assert_eq!(err.into_owned().original(), Some(Span::new(1, 2)));
+    }
+
+    #[test]
+    fn codes_and_expected() {
+        let err = TomlValue::try_from("a = [1 \"b\"]").unwrap_err();
+
+        assert_eq!(err.kind().code(), "E0002");
+        assert_eq!(err.expected(), ["`,`", "`]`"]);
+        assert_eq!(
+            err.to_string(),
+            "Unexpected `\"`, expected `,` or `]` at line: 1, column: 8"
+        );
+        assert!(err.render("a = [1 \"b\"]").starts_with("error[E0002]: "));
+
+        for (source, message) in [
+            ("a = { b = 1 = }", "Unexpected `=`, expected `,` or `}`"),
+            ("[a]]", "Unexpected `]`, expected a new line"),
+            ("= 1", "Unexpected `=`, expected a key"),
+            ("a = 1\n  = 1", "Unexpected `=`, expected a key"),
+            ("a", "Unexpected end of input, expected `=`"),
+            ("a =", "Expected a value, found end of input"),
+            ("a = \nb = 1", "Expected a value, found a new line"),
+        ] {
+            let err = TomlValue::try_from(source).unwrap_err();
+            assert_eq!(err.message(), message, "{:?}", source);
+        }
+
+        assert_eq!(
+            one_of(&[
+                Expected::Token(Token::Comma),
+                Expected::Key,
+                Expected::Value
+            ]),
+            "`,`, a key or a value"
+        );
+        assert_eq!(ErrorKind::NameUsed.to_string(), "E0003 duplicate key");
+    }
+}
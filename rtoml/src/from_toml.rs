@@ -0,0 +1,142 @@
+use crate::error::{FromTomlError, FromTomlErrorKind};
+use crate::{DateTime, TomlValue};
+
+use std::convert::TryFrom;
+
+// conversions used by `#[derive(FromToml)]` for the fields of a struct, they
+// can also be used on their own to extract a value
+macro_rules! integer {
+    ( $( $ty : ty ),* ) => {
+        $(
+            impl TryFrom<&TomlValue<'_>> for $ty {
+                type Error = FromTomlError;
+
+                fn try_from(value: &TomlValue<'_>) -> Result<Self, Self::Error> {
+                    let int = value
+                        .as_int()
+                        .ok_or_else(|| FromTomlError::mismatch("integer", value))?;
+
+                    <$ty>::try_from(*int)
+                        .map_err(|_| FromTomlErrorKind::OutOfRange(stringify!($ty)).into())
+                }
+            }
+        )*
+    };
+}
+
+integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl TryFrom<&TomlValue<'_>> for f64 {
+    type Error = FromTomlError;
+
+    fn try_from(value: &TomlValue<'_>) -> Result<Self, Self::Error> {
+        match value {
+            TomlValue::Float(x) => Ok(*x),
+            TomlValue::Int(x) => Ok(*x as f64),
+            _ => Err(FromTomlError::mismatch("float", value)),
+        }
+    }
+}
+
+impl TryFrom<&TomlValue<'_>> for f32 {
+    type Error = FromTomlError;
+
+    fn try_from(value: &TomlValue<'_>) -> Result<Self, Self::Error> {
+        let x = f64::try_from(value)?;
+
+        // `inf` and `nan` are kept, but a finite value too large for an `f32`
+        // would silently turn into an infinity
+        if x.is_finite() && !(x as f32).is_finite() {
+            return Err(FromTomlErrorKind::OutOfRange("f32").into());
+        }
+
+        Ok(x as f32)
+    }
+}
+
+impl TryFrom<&TomlValue<'_>> for bool {
+    type Error = FromTomlError;
+
+    fn try_from(value: &TomlValue<'_>) -> Result<Self, Self::Error> {
+        value
+            .as_boolean()
+            .copied()
+            .ok_or_else(|| FromTomlError::mismatch("boolean", value))
+    }
+}
+
+impl TryFrom<&TomlValue<'_>> for String {
+    type Error = FromTomlError;
+
+    fn try_from(value: &TomlValue<'_>) -> Result<Self, Self::Error> {
+        value
+            .as_string()
+            .cloned()
+            .ok_or_else(|| FromTomlError::mismatch("string", value))
+    }
+}
+
+impl TryFrom<&TomlValue<'_>> for DateTime {
+    type Error = FromTomlError;
+
+    fn try_from(value: &TomlValue<'_>) -> Result<Self, Self::Error> {
+        value
+            .as_datetime()
+            .cloned()
+            .ok_or_else(|| FromTomlError::mismatch("datetime", value))
+    }
+}
+
+impl<'v, 'a, T> TryFrom<&'v TomlValue<'a>> for Vec<T>
+where
+    T: TryFrom<&'v TomlValue<'a>, Error = FromTomlError>,
+{
+    type Error = FromTomlError;
+
+    fn try_from(value: &'v TomlValue<'a>) -> Result<Self, Self::Error> {
+        let array = value
+            .as_array()
+            .ok_or_else(|| FromTomlError::mismatch("array", value))?;
+
+        array
+            .iter()
+            .enumerate()
+            .map(|(index, value)| T::try_from(value).map_err(|err| err.at_index(index)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primitives() {
+        let value =
+            TomlValue::try_from("a = 300\nb = [1.5, 2]\nc = \"x\"\nd = 1e300\ne = inf").unwrap();
+        let table = value.as_table().unwrap();
+        let get = |key: &'static str| &table[&crate::TomlKey::from(key)];
+
+        assert_eq!(u16::try_from(get("a")), Ok(300));
+        assert_eq!(Vec::<f64>::try_from(get("b")), Ok(vec![1.5, 2.0]));
+        assert_eq!(String::try_from(get("c")), Ok(String::from("x")));
+
+        assert_eq!(
+            u8::try_from(get("a")).unwrap_err().to_string(),
+            "value out of range for u8"
+        );
+        assert_eq!(
+            f32::try_from(get("d")).unwrap_err().to_string(),
+            "value out of range for f32"
+        );
+        assert_eq!(f32::try_from(get("e")), Ok(f32::INFINITY));
+        assert_eq!(
+            Vec::<String>::try_from(get("b")).unwrap_err().to_string(),
+            "[0]: expected string, found float"
+        );
+        assert_eq!(
+            bool::try_from(get("c")).unwrap_err().at("c").to_string(),
+            "c: expected boolean, found string"
+        );
+    }
+}
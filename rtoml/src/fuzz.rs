@@ -0,0 +1,112 @@
+use crate::document::DocumentMut;
+use crate::emitter::Emitter;
+use crate::lexer::{lex as lex_tokens, Token};
+use crate::{lint, parse_with_diagnostics, TomlValue};
+
+use std::convert::TryFrom;
+use std::str;
+
+// the tokens are in order, don't overlap and literals are the bytes they
+// span, errors point inside of the input
+pub fn lex(data: &[u8]) {
+    match lex_tokens(data) {
+        Ok(tokens) => {
+            let mut end = 0;
+
+            for (token, span) in &tokens {
+                assert!(span.start >= end, "{:?} overlaps the previous token", span);
+                assert!(span.start < span.end && span.end <= data.len());
+
+                if let Token::Literal(x) = token {
+                    assert_eq!(x.as_bytes(), &data[span.start..span.end]);
+                }
+
+                end = span.end;
+            }
+        }
+        Err(err) => {
+            if let Some(span) = err.span() {
+                assert!(span.start <= span.end && span.start <= data.len());
+            }
+        }
+    }
+}
+
+// nothing panics, errors render and a valid document is parsed to the same
+// value once written back out, an editable document to the same text
+pub fn parse(data: &[u8]) {
+    let source = match str::from_utf8(data) {
+        Ok(source) => source,
+        Err(_) => return,
+    };
+
+    match TomlValue::try_from(source) {
+        Ok(value) => {
+            round_trip(&value);
+
+            let document = DocumentMut::parse(source).unwrap();
+            assert_eq!(document.to_string(), source);
+        }
+        Err(err) => {
+            let _ = err.render(source);
+        }
+    }
+
+    for diagnostic in parse_with_diagnostics(source).1 {
+        let _ = diagnostic.render(source);
+    }
+
+    for warning in lint::lint(source) {
+        let _ = warning.render(source);
+    }
+}
+
+// `value` is a document, a table
+pub fn round_trip(value: &TomlValue) {
+    // the parser only returns tables
+    let emitted = Emitter::new(false).emit(value.as_table().unwrap());
+    let parsed = TomlValue::try_from(emitted.as_str())
+        .unwrap_or_else(|err| panic!("{} in\n{}", err, emitted));
+
+    assert!(same(value, &parsed), "{:?} became {:?}", value, parsed);
+}
+
+// `==` with every nan equal to any other
+pub fn same(a: &TomlValue, b: &TomlValue) -> bool {
+    match (a, b) {
+        (TomlValue::Float(a), TomlValue::Float(b)) => a == b || (a.is_nan() && b.is_nan()),
+        (TomlValue::Array(a), TomlValue::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
+        }
+        (TomlValue::Table(a), TomlValue::Table(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).is_some_and(|b| same(a, b)))
+        }
+        (a, b) => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    // replays the seed corpus of the fuzz targets, so `cargo test` covers them
+    // without libfuzzer
+    #[test]
+    fn seeds() {
+        let seeds = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/seeds");
+        let mut count = 0;
+
+        for entry in fs::read_dir(seeds).unwrap() {
+            let data = fs::read(entry.unwrap().path()).unwrap();
+
+            super::lex(&data);
+            super::parse(&data);
+            count += 1;
+        }
+
+        assert!(count > 0);
+    }
+}
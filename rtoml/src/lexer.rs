@@ -1,11 +1,34 @@
 use self::Token::*;
+use crate::error::ErrLocation;
+use crate::parser::r_slice::RIndex;
 use crate::{TomlError, TomlKey};
 
 use std::fmt::{Display, Formatter};
 
-use simdutf8::basic::from_utf8;
+use simdutf8::compat::from_utf8;
 
-pub type TokenSized<'a> = (Token<'a>, usize);
+pub type TokenSized<'a> = (Token<'a>, Span);
+
+// byte range of a token in the lexed input
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
 
 #[derive(Debug, PartialEq, Copy, Clone, Eq)]
 pub enum Token<'a> {
@@ -31,13 +54,7 @@ impl<'a> Token<'a> {
     // Naming rules for keys and table are the same
     pub fn is_valid_table_name_or_key(&self) -> bool {
         if let Literal(literal) = self {
-            let trimmed = literal.trim();
-            let mut is_valid = false;
-            trimmed.as_bytes().iter().for_each(|e| match e {
-                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' => is_valid = true,
-                _ => is_valid = false,
-            });
-            is_valid
+            is_bare_key(literal.trim())
         } else {
             false
         }
@@ -58,9 +75,9 @@ impl<'a> Token<'a> {
 
     // convert a literal token into a key
     // otherwise none
-    pub fn as_key(&self) -> TomlKey {
+    pub fn as_key(&self) -> TomlKey<'a> {
         if let Literal(x) = self {
-            TomlKey::Literal(x.trim())
+            TomlKey::from(x.trim())
         } else {
             TomlKey::None
         }
@@ -91,18 +108,97 @@ impl<'a> From<Token<'a>> for char {
     }
 }
 
-pub fn lex(data: &[u8]) -> Result<Vec<TokenSized>, TomlError> {
+// bare keys may only contain ASCII letters, ASCII digits, underscores, and dashes
+// https://toml.io/en/v1.0.0#keys
+pub fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .bytes()
+            .all(|e| matches!(e, b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_'))
+}
+
+// the string the lexer is in, a `#` only starts a comment outside of one
+#[derive(Default)]
+struct Quotes {
+    // the quote and whether it's a multiline string
+    string: Option<(u8, bool)>,
+    // quotes left in the run that opened or closed a string
+    skip: usize,
+    // the previous token was a backslash in a basic string
+    escaped: bool,
+}
+
+impl Quotes {
+    // called with the first byte of every token
+    fn update(&mut self, data: &[u8], index: usize) {
+        let byte = data[index];
+        let run = |max: usize| {
+            data[index..]
+                .iter()
+                .take(max)
+                .take_while(|x| **x == byte)
+                .count()
+        };
+
+        if self.skip > 0 {
+            self.skip -= 1;
+            return;
+        }
+
+        if self.escaped {
+            self.escaped = false;
+            return;
+        }
+
+        match self.string {
+            None if matches!(byte, b'"' | b'\'') => match run(3) {
+                3 => {
+                    self.string = Some((byte, true));
+                    self.skip = 2;
+                }
+                // an empty string
+                2 => self.skip = 1,
+                _ => self.string = Some((byte, false)),
+            },
+            Some((quote, false)) if byte == quote => self.string = None,
+            // the last three quotes of a run close a multiline string, up to
+            // two before them are part of it
+            Some((quote, true)) if byte == quote && run(3) == 3 => {
+                self.string = None;
+                self.skip = run(usize::MAX) - 1;
+            }
+            Some((b'"', _)) if byte == b'\\' => self.escaped = true,
+            // the parser reports the unterminated string
+            Some((_, false)) if matches!(byte, b'\n' | b'\r') => self.string = None,
+            _ => (),
+        }
+    }
+}
+
+// the byte at `offset`, lexing errors are found before any position is known
+// so the line and column are counted up to it
+fn located<'a>(data: &[u8], offset: usize) -> ErrLocation<'a> {
+    ErrLocation::at(RIndex::locate(data, offset)).with_span(Span::new(offset, offset + 1))
+}
+
+pub fn lex(data: &[u8]) -> Result<Vec<TokenSized<'_>>, TomlError<'_>> {
     let mut lexemes: Vec<TokenSized> = Vec::new();
     let mut peekable = data.iter().peekable();
+    let mut quotes = Quotes::default();
 
     let mut index = 0;
 
     while let Some(byte) = peekable.next() {
+        quotes.update(data, index);
+
         match get_special_byte(*byte) {
-            Some(Hash) => {
+            Some(Hash) if quotes.string.is_none() => {
+                // the line break ends the comment, `\r\n` included
                 while let Some(x) = peekable.peek() {
-                    if **x == b'\n' {
+                    if matches!(**x, b'\n' | b'\r') {
                         break;
+                    } else if is_control(**x) {
+                        return Err(TomlError::ControlCharacter(located(data, index + 1)));
                     } else {
                         index += 1;
                         peekable.next();
@@ -110,18 +206,17 @@ pub fn lex(data: &[u8]) -> Result<Vec<TokenSized>, TomlError> {
                 }
             }
             Some(CarriageReturn) => {
-                let entry = if let Some(b'\n') = peekable.peek() {
+                if let Some(b'\n') = peekable.peek() {
                     peekable.next();
+                    lexemes.push((LineBreak, Span::new(index, index + 2)));
                     index += 1;
-                    (LineBreak, 1)
                 } else {
-                    (CarriageReturn, 1)
-                };
-
-                lexemes.push(entry)
+                    return Err(TomlError::ControlCharacter(located(data, index)));
+                }
             }
-            Some(x) => lexemes.push((x, 1)),
+            Some(x) => lexemes.push((x, Span::new(index, index + 1))),
             _ => {
+                let start = index;
                 let mut alphabetic_index = 0;
                 while let Some(x) = peekable.peek() {
                     if get_special_byte(**x).is_none() {
@@ -137,9 +232,17 @@ pub fn lex(data: &[u8]) -> Result<Vec<TokenSized>, TomlError> {
 
                 index += alphabetic_index;
 
-                let string = from_utf8(string_bytes)?;
+                if let Some(control) = string_bytes.iter().position(|x| is_control(*x)) {
+                    let control = start + control;
+                    return Err(TomlError::ControlCharacter(located(data, control)));
+                }
 
-                lexemes.push((Literal(string), string.len()))
+                let string = from_utf8(string_bytes).map_err(|err| {
+                    let invalid = start + err.valid_up_to();
+                    TomlError::Utf8Error(located(data, invalid))
+                })?;
+
+                lexemes.push((Literal(string), Span::new(start, relative_index)))
             }
         };
 
@@ -149,6 +252,12 @@ pub fn lex(data: &[u8]) -> Result<Vec<TokenSized>, TomlError> {
     Ok(lexemes)
 }
 
+// control characters, tab is the only one allowed in a document
+// https://toml.io/en/v1.0.0#comment
+fn is_control(byte: u8) -> bool {
+    (byte < 0x20 && byte != b'\t') || byte == 0x7F
+}
+
 fn get_special_byte<'a>(n: u8) -> Option<Token<'a>> {
     match n {
         b'=' => Some(Eq),
@@ -170,7 +279,7 @@ fn get_special_byte<'a>(n: u8) -> Option<Token<'a>> {
 impl Display for Token<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Literal(x) => write!(f, "Literal({})", x),
+            Literal(x) => write!(f, "{:?}", x),
             _ => {
                 let character: char = (*self).into();
                 write!(f, "{:?}", character)
@@ -186,15 +295,84 @@ pub mod test {
     #[test]
     pub fn basic_parsing() {
         let str = "hello world";
-        assert_eq!((Literal(str), str.len()), lex(str.as_bytes()).unwrap()[0])
+        assert_eq!(
+            (Literal(str), Span::new(0, str.len())),
+            lex(str.as_bytes()).unwrap()[0]
+        )
     }
 
     #[test]
     pub fn basic_special_chars() {
-        let str = "# hello world";
+        // comments are skipped up to the line break
+        let str = "# hello world\n[";
         assert_eq!(
-            [(Hash, 1), (Literal(" hello world"), " hello world".len())].to_vec(),
+            [(LineBreak, Span::new(13, 14)), (Sbo, Span::new(14, 15))].to_vec(),
             lex(str.as_bytes()).unwrap()
         )
     }
+
+    #[test]
+    pub fn spans() {
+        let str = "a = 1\r\nb # c\r\n=";
+        assert_eq!(
+            [
+                (Literal("a "), Span::new(0, 2)),
+                (Eq, Span::new(2, 3)),
+                (Literal(" 1"), Span::new(3, 5)),
+                (LineBreak, Span::new(5, 7)),
+                (Literal("b "), Span::new(7, 9)),
+                (LineBreak, Span::new(12, 14)),
+                (Eq, Span::new(14, 15)),
+            ]
+            .to_vec(),
+            lex(str.as_bytes()).unwrap()
+        );
+
+        let err = lex(b"a = \"\xFF\"").unwrap_err();
+        assert!(matches!(err, TomlError::Utf8Error(_)));
+        assert_eq!(err.span(), Some(Span::new(5, 6)));
+    }
+
+    #[test]
+    pub fn hash_in_strings() {
+        for (str, hashes) in [
+            ("a = \"b # c\" # d", 1),
+            ("a = 'b # c' # d", 1),
+            ("\"# a\" = 1 # b", 1),
+            ("a = \"b \\\" # c\" # d", 1),
+            ("a = \"\\\\\" # d", 0),
+            ("a = 'b\\' # d", 0),
+            ("a = \"\" # d", 0),
+            ("a = \"\"\"b\n\" # \"\"\" # d", 1),
+            ("a = '''b''''' # d", 0),
+            // the string ends at the line break, unterminated
+            ("a = \"b\n# c", 0),
+        ] {
+            let lexed = lex(str.as_bytes()).unwrap();
+            let count = lexed.iter().filter(|(token, _)| *token == Hash).count();
+            assert_eq!(count, hashes, "{:?}", str);
+        }
+    }
+
+    #[test]
+    pub fn control_characters() {
+        assert!(lex(b"a = \"\tb\" # \t").is_ok());
+
+        for (str, at) in [
+            (&b"a = \"\0\""[..], 5),
+            (b"a = 1 # \x7F", 8),
+            (b"a = 1 # \x1F\n", 8),
+            (b"a = 1\rb = 2", 5),
+        ] {
+            let err = lex(str).unwrap_err();
+            assert!(matches!(err, TomlError::ControlCharacter(_)));
+            assert_eq!(err.span(), Some(Span::new(at, at + 1)));
+        }
+
+        let err = lex(b"a = 1\nb = \"c\x01\"").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid control character while lexing at line: 2, column: 7"
+        );
+    }
 }
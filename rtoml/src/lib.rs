@@ -1,28 +1,58 @@
 //! `RToml`
 extern crate core;
 
-use crate::error::TomlError;
+use crate::diagnostic::Diagnostic;
+use crate::emitter::{format_value, Emitter};
+use crate::error::{ErrLocation, Limit, TomlError};
 use crate::lexer::lex;
 use crate::parser::r_iter::RIter;
+use crate::parser::r_slice::RIndex;
 use crate::parser::ParsedValue;
 
+use std::borrow::Cow;
 use std::convert::TryFrom;
 use std::fmt::{Display, Formatter};
+use std::hash::BuildHasherDefault;
+use std::str::FromStr;
 
-use rustc_hash::FxHashMap;
+use indexmap::IndexMap;
+use rustc_hash::FxHasher;
 
 mod builtins;
+#[cfg(feature = "serde")]
+pub mod de;
+mod emitter;
+mod from_toml;
 mod lexer;
 mod parser;
+#[cfg(feature = "serde")]
+pub mod ser;
 
+pub mod diagnostic;
+pub mod document;
 pub mod error;
+// bodies of the targets in `fuzz/`, they need the lexer and the emitter which
+// aren't public, not part of the api
+#[cfg(any(test, feature = "fuzz"))]
+#[doc(hidden)]
+pub mod fuzz;
+pub mod lint;
 pub mod prelude {
-    pub use crate::error::TomlError;
-    pub use crate::{DateTime, Table, TomlKey, TomlValue};
+    pub use crate::diagnostic::{Diagnostic, Severity};
+    pub use crate::document::DocumentMut;
+    pub use crate::error::{DocumentError, FromTomlError, OwnedTomlError, TomlError};
+    pub use crate::{DateTime, FormatOptions, IntoOwned, ParseOptions, Table, TomlKey, TomlValue};
     pub use std::convert::TryFrom;
 }
 
-pub type Table<'a> = FxHashMap<TomlKey<'a>, TomlValue<'a>>;
+#[cfg(feature = "serde")]
+pub use crate::de::from_str;
+#[cfg(feature = "serde")]
+pub use crate::ser::{to_string, to_string_pretty};
+
+// keys are kept in the order they were inserted, which for a parsed document
+// is the order they're defined in
+pub type Table<'a> = IndexMap<TomlKey<'a>, TomlValue<'a>, BuildHasherDefault<FxHasher>>;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum TomlValue<'a> {
@@ -37,10 +67,81 @@ pub enum TomlValue<'a> {
 
 #[derive(Debug, Clone, Eq, Hash, PartialEq)]
 pub enum TomlKey<'a> {
-    Literal(&'a str),
+    // quoted keys containing escape sequences own their content
+    Literal(Cow<'a, str>),
     None,
 }
 
+// bounds on what a document may contain, for input that can't be trusted,
+// going over one is a `TomlError::LimitExceeded`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    // arrays and inline tables inside of each other, the parser recurses
+    // into every one of them
+    pub max_depth: usize,
+    // bytes of the whole document
+    pub max_size: usize,
+    // bytes of a string once its escape sequences are replaced
+    pub max_string_length: usize,
+    // keys and table headers, counting the keys of inline tables
+    pub max_keys: usize,
+}
+
+impl ParseOptions {
+    // only the depth is bounded by default, enough for any real document
+    // while keeping the stack from overflowing
+    pub const DEFAULT: Self = Self {
+        max_depth: 128,
+        max_size: usize::MAX,
+        max_string_length: usize::MAX,
+        max_keys: usize::MAX,
+    };
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+// how `TomlValue::to_toml_string_with` and `DocumentMut::format` lay out a
+// document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    // spaces in a level of indentation
+    pub indent: usize,
+    // indent sub-tables and their keys by one level per parent table
+    pub indent_tables: bool,
+    // arrays going past this column are spread over one line per value,
+    // each followed by a comma
+    pub max_width: usize,
+    // pad the keys of a table so their `=` line up
+    pub align_entries: bool,
+    // tables with up to this many keys, none of them a table, are written
+    // inline, `0` gives every table a header
+    pub inline_tables: usize,
+    // write the keys of a table in alphabetical order instead of the order
+    // they're in
+    pub sort_keys: bool,
+}
+
+impl FormatOptions {
+    pub const DEFAULT: Self = Self {
+        indent: 4,
+        indent_tables: false,
+        max_width: 80,
+        align_entries: false,
+        inline_tables: 0,
+        sort_keys: false,
+    };
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum DateTime {
     DateTime(speedate::DateTime),
@@ -83,9 +184,40 @@ impl<'a> TomlValue<'a> {
         extract!(self, DateTime)
     }
 
-    pub fn as_table(&self) -> Option<&Table> {
+    pub fn as_table(&self) -> Option<&Table<'a>> {
         extract!(self, Table)
     }
+
+    // name of the value's type as used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            TomlValue::Int(_) => "integer",
+            TomlValue::Float(_) => "float",
+            TomlValue::String(_) => "string",
+            TomlValue::Array(_) => "array",
+            TomlValue::Boolean(_) => "boolean",
+            TomlValue::DateTime(_) => "datetime",
+            TomlValue::Table(_) => "table",
+        }
+    }
+
+    // the value written out as toml, a table as a document with `[table]`
+    // and `[[array]]` headers and anything else the way it's written after
+    // the `=` of a key/value
+    pub fn to_toml_string(&self) -> String {
+        match self {
+            TomlValue::Table(table) => Emitter::new(false).emit(table),
+            value => format_value(value),
+        }
+    }
+
+    // same as `to_toml_string`, laid out following `options`
+    pub fn to_toml_string_with(&self, options: &FormatOptions) -> String {
+        match self {
+            TomlValue::Table(table) => Emitter::with_options(options).emit(table),
+            value => Emitter::with_options(options).value_at(value, 0, ""),
+        }
+    }
 }
 
 impl<'a> TomlKey<'a> {
@@ -94,6 +226,66 @@ impl<'a> TomlKey<'a> {
     }
 }
 
+// detach a parsed value from the input it was parsed from, so it can be
+// stored in a `static` or sent to another thread
+pub trait IntoOwned {
+    type Owned: 'static;
+
+    fn into_owned(self) -> Self::Owned;
+}
+
+impl IntoOwned for TomlValue<'_> {
+    type Owned = TomlValue<'static>;
+
+    fn into_owned(self) -> Self::Owned {
+        match self {
+            TomlValue::Int(x) => TomlValue::Int(x),
+            TomlValue::Float(x) => TomlValue::Float(x),
+            TomlValue::String(x) => TomlValue::String(x),
+            TomlValue::Array(x) => {
+                TomlValue::Array(x.into_iter().map(IntoOwned::into_owned).collect())
+            }
+            TomlValue::Boolean(x) => TomlValue::Boolean(x),
+            TomlValue::DateTime(x) => TomlValue::DateTime(x),
+            TomlValue::Table(x) => TomlValue::Table(x.into_owned()),
+        }
+    }
+}
+
+impl IntoOwned for TomlKey<'_> {
+    type Owned = TomlKey<'static>;
+
+    fn into_owned(self) -> Self::Owned {
+        match self {
+            TomlKey::Literal(x) => TomlKey::Literal(Cow::Owned(x.into_owned())),
+            TomlKey::None => TomlKey::None,
+        }
+    }
+}
+
+impl IntoOwned for Table<'_> {
+    type Owned = Table<'static>;
+
+    fn into_owned(self) -> Self::Owned {
+        self.into_iter()
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect()
+    }
+}
+
+impl FromStr for DateTime {
+    type Err = speedate::ParseError;
+
+    // the value is tried as a date-time, then as a local date and lastly as
+    // a local time
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        speedate::DateTime::parse_str(str)
+            .map(DateTime::DateTime)
+            .or_else(|_| speedate::Date::parse_str(str).map(DateTime::Date))
+            .or_else(|_| speedate::Time::parse_str(str).map(DateTime::Time))
+    }
+}
+
 impl DateTime {
     pub fn as_datetime(&self) -> Option<&speedate::DateTime> {
         extract!(self, DateTime)
@@ -108,63 +300,51 @@ impl DateTime {
     }
 }
 
-fn hash_map_pretty_print(hashmap: &Table, padding_level: usize) -> String {
-    let padding = "  ".repeat(padding_level);
-    let mut key_value = String::new();
-    let mut braces = String::new();
-    let mut table_name = &TomlKey::None;
-
-    for (key, value) in hashmap.iter() {
-        if let Some(table) = value.as_table() {
-            table_name = key;
-            key_value.push_str(hash_map_pretty_print(table, padding_level + 1).as_str());
-        } else {
-            key_value.push_str(format!("{}{}: {},\n", padding.repeat(2), key, value).as_str());
-        }
+impl Display for TomlValue<'_> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+        fmt.write_str(&self.to_toml_string())
     }
+}
 
-    if table_name.is_none() {
-        braces.push_str(format!("{}{{\n", padding).as_str());
-        braces.push_str(&key_value);
-        braces.push_str(format!("{}}}\n", padding).as_str());
-    } else {
-        braces.push_str(format!("{}{}: {{\n", padding, table_name).as_str());
-        braces.push_str(&key_value);
-        braces.push_str(format!("{}}}\n", padding).as_str());
-    }
+// parse `str` without stopping at the first error, the returned value holds
+// everything outside of the statements that had errors
+pub fn parse_with_diagnostics(str: &str) -> (TomlValue<'_>, Vec<Diagnostic>) {
+    match lex(str.as_bytes()) {
+        Ok(lexed) => {
+            let (value, errors) =
+                ParsedValue::new(TomlValue::Int(0), RIter::new(&lexed)).parse_recovering();
 
-    braces
+            (value, errors.into_iter().map(Diagnostic::from).collect())
+        }
+        Err(err) => (
+            TomlValue::Table(Table::default()),
+            vec![Diagnostic::from(err)],
+        ),
+    }
 }
 
-impl Display for TomlValue<'_> {
-    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            TomlValue::Int(x) => fmt.write_str(x.to_string().as_str()),
-            TomlValue::Float(x) => fmt.write_str(x.to_string().as_str()),
-            TomlValue::String(x) => write!(fmt, "{:?}", x),
-            TomlValue::Boolean(x) => fmt.write_str(x.to_string().as_str()),
-            TomlValue::DateTime(x) => fmt.write_str(x.to_string().as_str()),
-            TomlValue::Array(x) => {
-                fmt.write_str("[")?;
-                for value in x {
-                    fmt.write_str(value.to_string().as_str())?;
-                    fmt.write_str(", ")?;
-                }
-                fmt.write_str("\x08\x08]")
-            }
-            TomlValue::Table(x) => {
-                writeln!(fmt, "{}", hash_map_pretty_print(x, 0))
-            }
-        }
+// parse `str` within the limits of `options`, the size is checked before
+// anything is lexed
+pub fn parse_with<'a>(
+    str: &'a str,
+    options: &ParseOptions,
+) -> Result<TomlValue<'a>, TomlError<'a>> {
+    if str.len() > options.max_size {
+        return Err(TomlError::LimitExceeded(
+            ErrLocation::at(RIndex::locate(str.as_bytes(), options.max_size)),
+            Limit::Size(options.max_size),
+        ));
     }
+
+    let lexed = lex(str.as_bytes())?;
+    ParsedValue::new(TomlValue::Int(0), RIter::with_options(&lexed, options)).parse()
 }
 
 impl<'a> TryFrom<&'a str> for TomlValue<'a> {
     type Error = TomlError<'a>;
 
     fn try_from(str: &'a str) -> Result<Self, Self::Error> {
-        let lexed = lex(str.as_bytes())?.leak();
-        ParsedValue::new(TomlValue::Int(0), RIter::new(lexed)).parse()
+        parse_with(str, &ParseOptions::DEFAULT)
     }
 }
 
@@ -181,8 +361,8 @@ impl Display for DateTime {
 impl Display for TomlKey<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let val = match self {
-            Self::None => format!(""),
-            Self::Literal(x) => format!("{}", x),
+            Self::None => String::new(),
+            Self::Literal(x) => x.to_string(),
         };
 
         write!(f, "{}", val)
@@ -191,6 +371,156 @@ impl Display for TomlKey<'_> {
 
 impl<'a> From<&'a str> for TomlKey<'a> {
     fn from(str: &'a str) -> Self {
-        Self::Literal(str)
+        Self::Literal(Cow::Borrowed(str))
+    }
+}
+
+impl From<String> for TomlKey<'_> {
+    fn from(string: String) -> Self {
+        Self::Literal(Cow::Owned(string))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ErrorKind, Span};
+
+    use std::thread;
+
+    fn read_config(config: String) -> TomlValue<'static> {
+        TomlValue::try_from(config.as_str()).unwrap().into_owned()
+    }
+
+    #[test]
+    fn owned_values() {
+        let value = read_config(String::from("[server]\nport = 80\n"));
+
+        let port = thread::spawn(move || {
+            value.as_table().unwrap()[&TomlKey::from("server")]
+                .as_table()
+                .unwrap()[&TomlKey::from("port")]
+                .clone()
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(port, TomlValue::Int(80));
+    }
+
+    #[test]
+    fn to_toml_string() {
+        let toml = r#"
+"key with spaces" = "tab\t \"quote\" \\ \u0001"
+floats = [inf, -inf, nan, -0.0, 1.0, 1e100]
+dates = [1979-05-27T07:32:00Z, 1979-05-27T00:32:00.5-07:00, 1979-05-27, 07:32:00]
+
+[server]
+"127.0.0.1" = { port = 80, tags = [] }
+
+[server.empty]
+
+[[server.routes]]
+path = "/"
+
+[[server.routes]]
+"#;
+        let value = TomlValue::try_from(toml).unwrap();
+        let written = value.to_toml_string();
+
+        assert_eq!(
+            written,
+            r#""key with spaces" = "tab\t \"quote\" \\ \u0001"
+floats = [inf, -inf, nan, -0.0, 1.0, 1e100]
+dates = [1979-05-27T07:32:00Z, 1979-05-27T00:32:00.5-07:00, 1979-05-27, 07:32:00]
+
+[server."127.0.0.1"]
+port = 80
+tags = []
+
+[server.empty]
+
+[[server.routes]]
+path = "/"
+
+[[server.routes]]
+"#
+        );
+        assert!(crate::fuzz::same(
+            &TomlValue::try_from(written.as_str()).unwrap(),
+            &value
+        ));
+        assert_eq!(value.to_string(), written);
+
+        // values on their own are written the way they are after a `=`
+        assert_eq!(TomlValue::String("a\nb".into()).to_string(), r#""a\nb""#);
+        assert_eq!(TomlValue::Float(2.0).to_string(), "2.0");
+        assert_eq!(
+            TomlValue::Array(vec![TomlValue::Int(1), TomlValue::Boolean(false)]).to_string(),
+            "[1, false]"
+        );
+    }
+
+    #[test]
+    fn limits() {
+        fn limit(source: &str, options: &ParseOptions) -> (Option<Limit>, Option<Span>) {
+            let err = parse_with(source, options).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+            (err.limit(), err.span())
+        }
+
+        // deep enough to overflow the stack without a limit
+        let source = format!("a = {}", "[".repeat(100_000));
+        let err = TomlValue::try_from(source.as_str()).unwrap_err();
+        assert_eq!(err.limit(), Some(Limit::Depth(128)));
+        assert_eq!(err.span(), Some(Span::new(132, 133)));
+
+        let nested = |depth| format!("a = {}1{}", "[{b = ".repeat(depth), "}]".repeat(depth));
+        let options = ParseOptions {
+            max_depth: 4,
+            ..ParseOptions::default()
+        };
+        assert!(TomlValue::try_from(nested(64).as_str()).is_ok());
+        assert!(parse_with(&nested(2), &options).is_ok());
+        assert_eq!(limit(&nested(3), &options).0, Some(Limit::Depth(4)));
+        // the depth goes back down after each value
+        assert!(parse_with("a = [[[[1]]], [[[2]]]]\nb = {c = [[[3]]]}", &options).is_ok());
+
+        let options = ParseOptions {
+            max_size: 8,
+            ..ParseOptions::default()
+        };
+        assert!(parse_with("a = 1\n", &options).is_ok());
+        let err = parse_with("a = 1\nb = 2\n", &options).unwrap_err();
+        assert_eq!(err.limit(), Some(Limit::Size(8)));
+        assert_eq!(err.span(), Some(Span::new(8, 8)));
+        assert_eq!(
+            err.to_string(),
+            "Exceeded the limit of 8 bytes per document at line: 2, column: 3"
+        );
+
+        let options = ParseOptions {
+            max_string_length: 3,
+            ..ParseOptions::default()
+        };
+        assert!(parse_with("a = \"abc\"\nb = '''\nabc'''", &options).is_ok());
+        assert_eq!(
+            limit("a = 1\nb = \"ab\\u00e9\"", &options),
+            (Some(Limit::StringLength(3)), Some(Span::new(10, 20)))
+        );
+        assert_eq!(
+            limit("a = { \"abcd\" = 1 }", &options),
+            (Some(Limit::StringLength(3)), Some(Span::new(6, 12)))
+        );
+
+        let options = ParseOptions {
+            max_keys: 3,
+            ..ParseOptions::default()
+        };
+        assert!(parse_with("[a]\nb.c = 1\nd = 2", &options).is_ok());
+        assert_eq!(
+            limit("[a]\nb = { c = 1, d = 2 }", &options),
+            (Some(Limit::Keys(3)), Some(Span::new(17, 18)))
+        );
     }
 }
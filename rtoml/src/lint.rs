@@ -0,0 +1,443 @@
+use crate::builtins::key::parse_dotted_key;
+use crate::diagnostic::Diagnostic;
+use crate::emitter::format_key;
+use crate::error::Span;
+use crate::lexer::lex;
+use crate::parser::r_iter::RIter;
+use crate::parser::{ParsedValue, Spans};
+use crate::{TomlKey, TomlValue};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+// integers above 2^53 can't all be represented by a f64, which is what
+// javascript and a lot of json tooling reads them as
+const MAX_SAFE_INTEGER: u64 = 1 << 53;
+
+// suspicious but valid toml, every lint is a warning with its own code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    // `[1, "a"]`
+    MixedArray,
+    // `name` and `Name` in the same table
+    KeyCase,
+    // lines longer than `LintOptions::max_line_length`
+    LongLine,
+    // `[a]`, `[b]`, `[a.c]`, the tables of `a` aren't next to each other
+    TableOrder,
+    // integers that lose precision when read as a float
+    ImpreciseInteger,
+    // keys missing from `LintOptions::known_keys`
+    UnknownKey,
+}
+
+impl Lint {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Lint::MixedArray => "W0001",
+            Lint::KeyCase => "W0002",
+            Lint::LongLine => "W0003",
+            Lint::TableOrder => "W0004",
+            Lint::ImpreciseInteger => "W0005",
+            Lint::UnknownKey => "W0006",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LintOptions {
+    pub max_line_length: usize,
+    // dotted keys a document may have, written as they would be in the
+    // document so `"a.b".c` is `c` in the table `a.b`. `server.port` also
+    // allows `server`, while `server` allows anything under it. `None`
+    // disables the lint
+    pub known_keys: Option<Vec<String>>,
+    pub allow: Vec<Lint>,
+}
+
+impl Default for LintOptions {
+    fn default() -> Self {
+        Self {
+            max_line_length: 100,
+            known_keys: None,
+            allow: Vec::new(),
+        }
+    }
+}
+
+// lint `source` with the default options
+pub fn lint(source: &str) -> Vec<Diagnostic> {
+    lint_with(source, &LintOptions::default())
+}
+
+// warnings about `source` sorted by position, errors aren't reported here,
+// see `parse_with_diagnostics`, but whatever could be parsed is still linted
+pub fn lint_with(source: &str, options: &LintOptions) -> Vec<Diagnostic> {
+    let lexed = match lex(source.as_bytes()) {
+        Ok(lexed) => lexed,
+        Err(_) => return Vec::new(),
+    };
+    let (value, _, spans) = ParsedValue::new(TomlValue::Int(0), RIter::new(&lexed)).parse_spans();
+
+    let mut linter = Linter::new(options, &spans);
+
+    linter.lines(source);
+    linter.table_order(&spans);
+    linter.value(&value, &mut Vec::new());
+
+    if let Some(known_keys) = &options.known_keys {
+        // a key that can't be parsed is taken as a single segment
+        let known: Vec<Vec<String>> = known_keys
+            .iter()
+            .map(|key| parse_dotted_key(key).unwrap_or_else(|| vec![key.clone()]))
+            .collect();
+        linter.unknown_keys(&value, &known, &mut Vec::new());
+    }
+
+    let mut diagnostics = linter.diagnostics;
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.map(|span| span.start));
+    diagnostics
+}
+
+struct Linter<'o, 'a> {
+    options: &'o LintOptions,
+    // where each path was first defined, by a key or a header
+    defined: FxHashMap<&'o [TomlKey<'a>], Span>,
+    reported: FxHashSet<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'o, 'a> Linter<'o, 'a> {
+    fn new(options: &'o LintOptions, spans: &'o Spans<'a>) -> Self {
+        let mut defined = FxHashMap::default();
+
+        for (path, span) in spans.keys.iter().chain(&spans.headers) {
+            defined.entry(path.as_slice()).or_insert(*span);
+        }
+
+        Self {
+            options,
+            defined,
+            reported: FxHashSet::default(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn warn(&mut self, lint: Lint, message: String, span: Option<Span>) {
+        if !self.options.allow.contains(&lint) {
+            self.diagnostics
+                .push(Diagnostic::warning(lint.code(), message, span));
+        }
+    }
+
+    // keys of inline tables aren't recorded, they resolve to the closest
+    // parent that is
+    fn span(&self, path: &[TomlKey<'a>]) -> Option<Span> {
+        (1..=path.len())
+            .rev()
+            .find_map(|len| self.defined.get(&path[..len]).copied())
+    }
+
+    fn lines(&mut self, source: &str) {
+        let mut offset = 0;
+
+        for line in source.split('\n') {
+            let content = line.trim_end_matches('\r');
+            let length = content.chars().count();
+
+            if length > self.options.max_line_length {
+                // unwrap is fine, the line has more characters than that
+                let (start, _) = content
+                    .char_indices()
+                    .nth(self.options.max_line_length)
+                    .unwrap();
+                self.warn(
+                    Lint::LongLine,
+                    format!(
+                        "line is {} characters long, more than {}",
+                        length, self.options.max_line_length
+                    ),
+                    Some(Span::new(offset + start, offset + content.len())),
+                );
+            }
+
+            offset += line.len() + 1;
+        }
+    }
+
+    // every header of a top level table should come before the next top
+    // level table is started
+    fn table_order(&mut self, spans: &Spans<'a>) {
+        let mut seen = FxHashSet::default();
+        let mut current = None;
+
+        for (path, span) in &spans.headers {
+            let top = &path[0];
+
+            if current != Some(top) && !seen.insert(top) {
+                self.warn(
+                    Lint::TableOrder,
+                    format!(
+                        "table `{}` is defined apart from the other `{}` tables",
+                        join(path),
+                        format_key(top)
+                    ),
+                    Some(*span),
+                );
+            }
+
+            current = Some(top);
+        }
+    }
+
+    fn value(&mut self, value: &TomlValue<'a>, path: &mut Vec<TomlKey<'a>>) {
+        match value {
+            TomlValue::Table(table) => {
+                let mut keys: Vec<_> = table.keys().collect();
+                keys.sort_by_key(|key| {
+                    let span = self.span(&child(path, key));
+                    (span.map(|span| span.start), key.to_string())
+                });
+
+                let mut cases: FxHashMap<String, &TomlKey> = FxHashMap::default();
+
+                for key in &keys {
+                    match cases.get(&key.to_string().to_lowercase()) {
+                        Some(other) => {
+                            let message = format!(
+                                "`{}` differs from `{}` only in case",
+                                format_key(key),
+                                format_key(other)
+                            );
+                            let span = self.span(&child(path, key));
+                            self.warn(Lint::KeyCase, message, span);
+                        }
+                        None => {
+                            cases.insert(key.to_string().to_lowercase(), key);
+                        }
+                    }
+                }
+
+                for key in keys {
+                    path.push(key.clone());
+                    self.value(&table[key], path);
+                    path.pop();
+                }
+            }
+            TomlValue::Array(array) => {
+                if let Some(first) = array.first() {
+                    if let Some(other) = array
+                        .iter()
+                        .find(|value| value.type_name() != first.type_name())
+                    {
+                        let message = format!(
+                            "array `{}` mixes {} and {} values",
+                            join(path),
+                            first.type_name(),
+                            other.type_name()
+                        );
+                        self.warn(Lint::MixedArray, message, self.span(path));
+                    }
+                }
+
+                for value in array {
+                    self.value(value, path);
+                }
+            }
+            TomlValue::Int(integer) if integer.unsigned_abs() > MAX_SAFE_INTEGER => {
+                let message = format!(
+                    "`{}` of `{}` loses precision when read as a float",
+                    integer,
+                    join(path)
+                );
+                self.warn(Lint::ImpreciseInteger, message, self.span(path));
+            }
+            _ => (),
+        }
+    }
+
+    fn unknown_keys(
+        &mut self,
+        value: &TomlValue<'a>,
+        known: &[Vec<String>],
+        path: &mut Vec<TomlKey<'a>>,
+    ) {
+        match value {
+            TomlValue::Table(table) => {
+                for (key, value) in table {
+                    path.push(key.clone());
+
+                    let segments: Vec<_> = path.iter().map(ToString::to_string).collect();
+                    let is_known = known.iter().any(|known| {
+                        known.len() <= segments.len()
+                            && known.iter().zip(&segments).all(|(a, b)| a == b)
+                    });
+                    let is_parent = known.iter().any(|known| {
+                        known.len() > segments.len()
+                            && known.iter().zip(&segments).all(|(a, b)| a == b)
+                    });
+
+                    if is_parent {
+                        self.unknown_keys(value, known, path);
+                    } else if !is_known && self.reported.insert(join(path)) {
+                        let message = format!("unknown key `{}`", join(path));
+                        self.warn(Lint::UnknownKey, message, self.span(path));
+                    }
+
+                    path.pop();
+                }
+            }
+            // arrays of tables
+            TomlValue::Array(array) => {
+                for value in array {
+                    self.unknown_keys(value, known, path);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn child<'a>(path: &[TomlKey<'a>], key: &TomlKey<'a>) -> Vec<TomlKey<'a>> {
+    let mut path = path.to_vec();
+    path.push(key.clone());
+    path
+}
+
+fn join(path: &[TomlKey]) -> String {
+    path.iter().map(format_key).collect::<Vec<_>>().join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.code)
+            .collect()
+    }
+
+    #[test]
+    fn lints() {
+        let source = "\
+a = [1, \"b\"]
+Name = 1
+name = 2
+big = 9007199254740993
+small = -9007199254740992
+[x]
+[y]
+z = { c = [[1], [true, 1.5]] }
+[x.w]
+";
+        let diagnostics = lint(source);
+
+        assert_eq!(
+            codes(&diagnostics),
+            ["W0001", "W0002", "W0005", "W0001", "W0004"]
+        );
+        assert!(diagnostics.iter().all(|diagnostic| !diagnostic.is_error()));
+        assert_eq!(
+            diagnostics[0].message,
+            "array `a` mixes integer and string values"
+        );
+        assert_eq!(diagnostics[1].span, Some(Span::new(22, 26)));
+        assert_eq!(
+            diagnostics[1].message,
+            "`name` differs from `Name` only in case"
+        );
+        assert_eq!(
+            diagnostics[3].message,
+            "array `y.z.c` mixes boolean and float values"
+        );
+        assert_eq!(
+            diagnostics[4].message,
+            "table `x.w` is defined apart from the other `x` tables"
+        );
+        assert!(diagnostics[4]
+            .render(source)
+            .ends_with("9 | [x.w]\n  |  ^^^\n"));
+
+        let options = LintOptions {
+            allow: vec![Lint::MixedArray, Lint::TableOrder],
+            ..LintOptions::default()
+        };
+        assert_eq!(codes(&lint_with(source, &options)), ["W0002", "W0005"]);
+    }
+
+    #[test]
+    fn long_lines() {
+        let options = LintOptions {
+            max_line_length: 10,
+            ..LintOptions::default()
+        };
+        let source = "a = \"éééé\"\r\nb = \"éééééé\"\r\n";
+        let diagnostics = lint_with(source, &options);
+
+        assert_eq!(codes(&diagnostics), ["W0003"]);
+        assert_eq!(
+            diagnostics[0].message,
+            "line is 12 characters long, more than 10"
+        );
+        assert!(diagnostics[0]
+            .render(source)
+            .ends_with("2 | b = \"éééééé\"\n  |           ^^\n"));
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let options = LintOptions {
+            known_keys: Some(vec![
+                String::from("name"),
+                String::from("server.port"),
+                String::from("routes.path"),
+                String::from("extra"),
+            ]),
+            ..LintOptions::default()
+        };
+        let source = "\
+name = 1
+nmae = 2
+extra = { anything = 1 }
+[server]
+port = 80
+host = \"a\"
+[[routes]]
+path = \"/\"
+[[routes]]
+path = \"/b\"
+method = \"GET\"
+[[routes]]
+method = \"POST\"
+";
+        let diagnostics = lint_with(source, &options);
+        let messages: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+
+        assert_eq!(
+            messages,
+            [
+                "unknown key `nmae`",
+                "unknown key `server.host`",
+                "unknown key `routes.method`"
+            ]
+        );
+        assert_eq!(diagnostics[0].span, Some(Span::new(9, 13)));
+        assert!(lint(source).is_empty());
+
+        let options = LintOptions {
+            known_keys: Some(vec![String::from("\"a.b\".c"), String::from("d . 'e.f'")]),
+            ..LintOptions::default()
+        };
+        let source = "\"a.b\".c = 1\n\"a.b\".x = 2\nd.\"e.f\" = 3\nd.e = 4\n";
+        let messages: Vec<_> = lint_with(source, &options)
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+
+        assert_eq!(messages, ["unknown key `\"a.b\".x`", "unknown key `d.e`"]);
+    }
+}
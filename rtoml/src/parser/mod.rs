@@ -1,9 +1,12 @@
+use crate::builtins::key::parse_key;
 use crate::builtins::parse_value;
-use crate::error::ErrLocation;
+use crate::error::{ErrLocation, Expected, Limit, Span};
 use crate::lexer::Token;
 use crate::parser::r_iter::RIter;
 use crate::{Table, TomlError, TomlKey, TomlValue};
 
+use std::slice;
+
 use rustc_hash::FxHashMap;
 
 pub mod r_iter;
@@ -14,110 +17,709 @@ macro_rules! expect {
         if !$iter.next_if_eq($token) {
             return Err(TomlError::UnexpectedCharacter(
                 ErrLocation::new(RIter::from($iter.as_slice())),
-                &[$token],
+                &[Expected::Token($token)],
             ));
         }
     };
 }
 
-pub struct ParsedValue<'a> {
+// how a table came to exist, which decides whether it may be defined again
+// https://toml.io/en/v1.0.0#table
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Defined {
+    // created as the parent of a `[table]` header, `[a.b]` implicitly creates `a`
+    Implicit,
+    // `[table]` header
+    Header,
+    // created by a dotted key, `a.b = 1` creates `a`
+    Dotted,
+    // `[[table]]` header
+    Array,
+    // `key = value`, the value can't be extended even if it's an inline
+    // table or an array
+    Value,
+}
+
+// every key and table defined so far along with where it was defined, so
+// a duplicate can point back at the original
+#[derive(Default)]
+pub struct Tables<'a> {
+    defined: FxHashMap<Vec<TomlKey<'a>>, (Defined, Span)>,
+}
+
+impl<'a> Tables<'a> {
+    fn get(&self, path: &[TomlKey<'a>]) -> Option<(Defined, Span)> {
+        self.defined.get(path).copied()
+    }
+
+    fn set(&mut self, path: &[TomlKey<'a>], defined: Defined, span: Span) {
+        self.defined.insert(path.to_vec(), (defined, span));
+    }
+
+    // headers create the tables leading up to them implicitly, they can't go
+    // through a value, the span of the value is returned if they do
+    fn define_parents(&mut self, path: &[TomlKey<'a>], span: Span) -> Result<(), Span> {
+        for index in 1..path.len() {
+            match self.get(&path[..index]) {
+                None => self.set(&path[..index], Defined::Implicit, span),
+                Some((Defined::Value, original)) => return Err(original),
+                Some(_) => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    // every `[[table]]` header starts a fresh table, forget what was defined
+    // inside of the previous one
+    fn clear_children(&mut self, path: &[TomlKey<'a>]) {
+        self.defined
+            .retain(|key, _| key.len() <= path.len() || !key.starts_with(path));
+    }
+}
+
+// where the headers and keys of a document were found, in document order,
+// paths are absolute but keys of inline tables aren't recorded
+#[derive(Default, Debug)]
+pub struct Spans<'a> {
+    pub headers: Vec<(Vec<TomlKey<'a>>, Span)>,
+    pub keys: Vec<(Vec<TomlKey<'a>>, Span)>,
+}
+
+// the state of a document between statements
+#[derive(Default)]
+struct Document<'a> {
+    value: Table<'a>,
+    tables: Tables<'a>,
+    // path of the table the following key/values belong to
+    section: Vec<TomlKey<'a>>,
+    // only recorded when asked for
+    spans: Option<Spans<'a>>,
+}
+
+impl<'a> Document<'a> {
+    // parse a header, a key/value or skip a token that doesn't start either
+    fn statement<'t>(&mut self, iter: &mut RIter<'t, 'a>) -> Result<(), TomlError<'a>> {
+        let token = match iter.peek() {
+            Some(token) => token,
+            None => return Ok(()),
+        };
+
+        match token {
+            (Token::Sbo, _) => {
+                iter.next();
+
+                let is_array = iter.next_if_eq(Token::Sbo);
+                let header = iter.clone();
+                let parsed = parse_key(iter.as_slice(), &[Expected::Token(Token::Sbc)])?;
+                *iter = parsed.section;
+                let span = key_span(header.clone(), iter.index.offset - 1);
+                count_key(iter, header.clone(), span)?;
+
+                if is_array {
+                    expect!(iter, Token::Sbc);
+                }
+
+                end_of_line(iter)?;
+
+                self.tables
+                    .define_parents(&parsed.path, span)
+                    .map_err(|original| name_used(header.clone(), span, Some(original)))?;
+
+                match (is_array, self.tables.get(&parsed.path)) {
+                    (true, None) | (true, Some((Defined::Array, _))) => {
+                        ParsedValue::push_table_array(&mut self.value, &parsed.path, header)?;
+                        self.tables.set(&parsed.path, Defined::Array, span);
+                        self.tables.clear_children(&parsed.path);
+                    }
+                    (false, None) | (false, Some((Defined::Implicit, _))) => {
+                        ParsedValue::get_table(&mut self.value, &parsed.path, header)?;
+                        self.tables.set(&parsed.path, Defined::Header, span);
+                    }
+                    (_, Some((_, original))) => {
+                        return Err(name_used(header, span, Some(original)));
+                    }
+                }
+
+                if let Some(spans) = &mut self.spans {
+                    spans.headers.push((parsed.path.clone(), span));
+                }
+
+                self.section = parsed.path;
+            }
+            // `= value` without a key
+            (Token::Eq, _) => {
+                return Err(TomlError::UnexpectedCharacter(
+                    ErrLocation::new(iter.clone()),
+                    &[Expected::Key],
+                ));
+            }
+            (Token::DoubleQuote, _) | (Token::SingleQuote, _) => {
+                self.key_value(iter)?;
+            }
+            (Token::Literal(_), _) if !token.0.is_space() => {
+                self.key_value(iter)?;
+            }
+            _ => {
+                iter.next();
+            }
+        }
+
+        Ok(())
+    }
+
+    // a key/value statement, recording where the key was
+    fn key_value<'t>(&mut self, iter: &mut RIter<'t, 'a>) -> Result<(), TomlError<'a>> {
+        let key = ParsedValue::key_value(iter, &mut self.value, &self.section, &mut self.tables)?;
+        end_of_line(iter)?;
+
+        if let Some(spans) = &mut self.spans {
+            spans.keys.push(key);
+        }
+
+        Ok(())
+    }
+}
+
+// headers and key/values are followed by whitespace and a comment at most
+fn end_of_line<'a>(iter: &mut RIter<'_, 'a>) -> Result<(), TomlError<'a>> {
+    while let Some((token, _)) = iter.peek() {
+        if Token::LineBreak == *token {
+            break;
+        } else if token.is_space() {
+            iter.next();
+        } else {
+            return Err(TomlError::UnexpectedCharacter(
+                ErrLocation::new(RIter::from(iter.as_slice())),
+                &[Expected::Token(Token::LineBreak)],
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// keys and headers count towards `ParseOptions::max_keys`, `location` is
+// where the key starts
+fn count_key<'a>(
+    iter: &mut RIter<'_, 'a>,
+    location: RIter<'_, 'a>,
+    span: Span,
+) -> Result<(), TomlError<'a>> {
+    let max_keys = iter.limits.options.max_keys;
+    iter.limits.keys += 1;
+
+    if iter.limits.keys > max_keys {
+        return Err(TomlError::LimitExceeded(
+            ErrLocation::new(location).with_span(span),
+            Limit::Keys(max_keys),
+        ));
+    }
+
+    Ok(())
+}
+
+fn name_used<'a>(location: RIter<'_, 'a>, span: Span, original: Option<Span>) -> TomlError<'a> {
+    TomlError::NameUsed(ErrLocation::new(location).with_span(span), original)
+}
+
+// span of the key starting at `iter` and ending before `end`, without the
+// whitespace around it
+pub(crate) fn key_span(iter: RIter, end: usize) -> Span {
+    let mut key: Option<Span> = None;
+
+    for (token, span) in iter {
+        if span.start >= end {
+            break;
+        }
+
+        let (start, stop) = match token {
+            Token::Literal(x) if x.trim().is_empty() => continue,
+            Token::Literal(x) => (
+                span.start + x.len() - x.trim_start().len(),
+                span.end - (x.len() - x.trim_end().len()),
+            ),
+            _ => (span.start, span.end),
+        };
+
+        key = Some(Span::new(key.map_or(start, |key| key.start), stop));
+    }
+
+    key.unwrap_or_else(|| Span::new(end, end))
+}
+
+pub struct ParsedValue<'t, 'a> {
     pub value: TomlValue<'a>,
-    pub section: RIter<'a>,
+    pub section: RIter<'t, 'a>,
 }
 
-impl<'a> ParsedValue<'a> {
-    pub fn new(value: TomlValue<'a>, section: RIter<'a>) -> Self {
+impl<'t, 'a> ParsedValue<'t, 'a> {
+    pub fn new(value: TomlValue<'a>, section: RIter<'t, 'a>) -> Self {
         Self { value, section }
     }
 
     pub fn parse(self) -> Result<TomlValue<'a>, TomlError<'a>> {
         let mut iter = self.section;
-        let mut value = FxHashMap::default();
-
-        while let Some(token) = iter.next() {
-            match token {
-                (Token::Sbo, _) => {
-                    if let Some((next, _)) = iter.next() {
-                        if *next == Token::Sbo {
-
-                            // table array segment
-                        } else if next.is_valid_table_name_or_key() {
-                            expect!(iter, Token::Sbc);
-
-                            while let Some((linebreak, _)) = iter.peek() {
-                                if Token::LineBreak == *linebreak {
-                                    iter.next();
-                                    break;
-                                } else if linebreak.is_space() {
-                                    continue;
-                                } else {
-                                    return Err(TomlError::UnexpectedCharacter(
-                                        ErrLocation::new(RIter::from(iter.as_slice())),
-                                        &[Token::Eq],
-                                    ));
-                                }
-                            }
-
-                            let mut table_content = FxHashMap::default();
-
-                            while let Some((token, _)) = iter.next() {
-                                Self::key_value(token, &mut iter, &mut table_content)?;
-
-                                if *token == Token::LineBreak {
-                                    if let Some((Token::Sbo, _)) = iter.peek() {
-                                        break;
-                                    }
-                                }
-                            }
-
-                            value.insert(next.as_key(), TomlValue::Table(table_content));
-                        }
-                    }
-                }
-                // Top level key and value declaration
-                (Token::Literal(_), _) => {
-                    let mut table_content = FxHashMap::default();
+        let mut document = Document::default();
 
-                    Self::key_value(&token.0, &mut iter, &mut table_content)?;
+        while iter.peek().is_some() {
+            document.statement(&mut iter)?;
+        }
 
-                    while let Some((token, _)) = iter.next() {
-                        if *token == Token::LineBreak {
-                            if let Some((Token::Sbo, _)) = iter.peek() {
-                                break;
-                            }
-                        }
+        Ok(TomlValue::Table(document.value))
+    }
 
-                        Self::key_value(token, &mut iter, &mut table_content)?;
-                    }
+    // keep parsing after an error, the statement that failed is skipped up
+    // to the end of the line the error was found on
+    pub fn parse_recovering(self) -> (TomlValue<'a>, Vec<TomlError<'a>>) {
+        let mut document = Document::default();
+        let errors = self.recover(&mut document);
 
-                    value.insert(TomlKey::None, TomlValue::Table(table_content));
-                }
-                _ => (),
+        (TomlValue::Table(document.value), errors)
+    }
+
+    // same as `parse_recovering`, also returning where keys were defined
+    pub fn parse_spans(self) -> (TomlValue<'a>, Vec<TomlError<'a>>, Spans<'a>) {
+        let mut document = Document {
+            spans: Some(Spans::default()),
+            ..Document::default()
+        };
+        let errors = self.recover(&mut document);
+
+        (
+            TomlValue::Table(document.value),
+            errors,
+            document.spans.unwrap_or_default(),
+        )
+    }
+
+    fn recover(self, document: &mut Document<'a>) -> Vec<TomlError<'a>> {
+        let mut iter = self.section;
+        let mut errors = Vec::new();
+
+        while iter.peek().is_some() {
+            let start = iter.clone();
+
+            if let Err(err) = document.statement(&mut iter) {
+                iter = start;
+                resync(&mut iter, err.span().map_or(0, |span| span.start));
+                errors.push(err);
             }
         }
 
-        Ok(TomlValue::Table(value))
+        errors
     }
 
+    // parse `key = value` where key may be dotted, the value is inserted in
+    // the table at `section` relative to `table`, the full path of the key
+    // and its span are returned
     pub fn key_value(
-        token: &'a Token<'a>,
-        iter: &mut RIter<'a>,
-        table_content: &mut Table<'a>,
+        iter: &mut RIter<'t, 'a>,
+        table: &mut Table<'a>,
+        section: &[TomlKey<'a>],
+        tables: &mut Tables<'a>,
+    ) -> Result<(Vec<TomlKey<'a>>, Span), TomlError<'a>> {
+        let location = iter.clone();
+        let key = parse_key(iter.as_slice(), &[Expected::Token(Token::Eq)])?;
+        let span = key_span(location.clone(), key.section.index.offset - 1);
+        let mut value = key.section;
+        count_key(&mut value, location.clone(), span)?;
+        let parsed = parse_value(value.as_slice())?;
+        *iter = parsed.section;
+
+        // the key parser never returns an empty path
+        let (last, parents) = key.path.split_last().unwrap();
+        let mut path = section.to_vec();
+        let mut table_content = Self::get_table(table, section, location.clone())?;
+
+        // dotted keys create tables that can only be extended by other
+        // dotted keys of the same table
+        for key in parents {
+            path.push(key.clone());
+
+            match tables.get(&path) {
+                None => tables.set(&path, Defined::Dotted, span),
+                Some((Defined::Dotted, _)) => (),
+                Some((_, original)) => return Err(name_used(location, span, Some(original))),
+            }
+
+            table_content = Self::get_table(table_content, slice::from_ref(key), location.clone())?;
+        }
+
+        path.push(last.clone());
+
+        if let Some((_, original)) = tables.get(&path) {
+            return Err(name_used(location, span, Some(original)));
+        }
+
+        tables.set(&path, Defined::Value, span);
+        table_content.insert(last.clone(), parsed.value);
+
+        Ok((path, span))
+    }
+
+    // walk down `path` creating the tables which don't exist yet, an array of
+    // tables resolves to its most recently defined element
+    // https://toml.io/en/v1.0.0#array-of-tables
+    fn get_table<'b>(
+        mut table: &'b mut Table<'a>,
+        path: &[TomlKey<'a>],
+        location: RIter<'t, 'a>,
+    ) -> Result<&'b mut Table<'a>, TomlError<'a>> {
+        for key in path {
+            let entry = table
+                .entry(key.clone())
+                .or_insert_with(|| TomlValue::Table(Table::default()));
+
+            table = match entry {
+                TomlValue::Table(table) => table,
+                TomlValue::Array(array) => match array.last_mut() {
+                    Some(TomlValue::Table(table)) => table,
+                    _ => return Err(TomlError::NameUsed(ErrLocation::new(location), None)),
+                },
+                _ => return Err(TomlError::NameUsed(ErrLocation::new(location), None)),
+            };
+        }
+
+        Ok(table)
+    }
+
+    // `[[path]]` appends a new table to the array at `path`
+    fn push_table_array(
+        table: &mut Table<'a>,
+        path: &[TomlKey<'a>],
+        location: RIter<'t, 'a>,
     ) -> Result<(), TomlError<'a>> {
-        if token.is_valid_table_name_or_key() && !token.is_space() {
-            if !iter.next_if_eq(Token::Eq) {
-                return Err(TomlError::UnexpectedCharacter(
-                    ErrLocation::new(RIter::from(iter.as_slice())),
-                    &[Token::Eq],
-                ));
+        // the key parser never returns an empty path
+        let (last, parents) = path.split_last().unwrap();
+        let parent = Self::get_table(table, parents, location.clone())?;
+
+        match parent
+            .entry(last.clone())
+            .or_insert_with(|| TomlValue::Array(Vec::new()))
+        {
+            TomlValue::Array(array) => {
+                array.push(TomlValue::Table(Table::default()));
+                Ok(())
+            }
+            _ => Err(TomlError::NameUsed(ErrLocation::new(location), None)),
+        }
+    }
+}
+
+// skip the statement `iter` starts at, up to the first line break at or
+// after `from` that isn't inside of an array or a string. Inline tables
+// can't span lines so they end at the line break
+fn resync(iter: &mut RIter, from: usize) {
+    let mut brackets = Vec::new();
+    // the quote of the string the tokens are in and if it's multiline
+    let mut string: Option<(Token, bool)> = None;
+
+    while let Some((token, span)) = iter.next() {
+        match (string, *token) {
+            (Some((Token::DoubleQuote, _)), Token::BackSlash) => {
+                iter.next();
+            }
+            (Some((quote, false)), token) if token == quote => string = None,
+            (Some((quote, true)), token) if token == quote => {
+                let mut quotes = 1;
+                while iter.next_if_eq(quote) {
+                    quotes += 1;
+                }
+
+                if quotes >= 3 {
+                    string = None;
+                }
             }
+            // single line strings end at the line break, unterminated
+            (Some((_, false)), Token::LineBreak) | (None, Token::LineBreak) => {
+                string = None;
 
-            let parsed = parse_value(iter.as_slice())?;
-            *iter = parsed.section;
+                while brackets.last() == Some(&Token::Cbo) {
+                    brackets.pop();
+                }
 
-            table_content.insert(token.as_key(), parsed.value);
+                if brackets.is_empty() && span.start >= from {
+                    break;
+                }
+            }
+            (None, quote @ (Token::DoubleQuote | Token::SingleQuote)) => {
+                if !iter.next_if_eq(quote) {
+                    string = Some((quote, false));
+                } else if iter.next_if_eq(quote) {
+                    string = Some((quote, true));
+                }
+            }
+            (None, token @ (Token::Sbo | Token::Cbo)) => brackets.push(token),
+            (None, Token::Sbc) if brackets.last() == Some(&Token::Sbo) => {
+                brackets.pop();
+            }
+            (None, Token::Cbc) if brackets.last() == Some(&Token::Cbo) => {
+                brackets.pop();
+            }
+            _ => (),
         }
+    }
+}
 
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use crate::error::{ErrorKind, Span};
+    use crate::{TomlKey, TomlValue};
+
+    use std::convert::TryFrom;
+
+    fn get<'a>(value: &'a TomlValue<'a>, key: &'a str) -> &'a TomlValue<'a> {
+        value.as_table().unwrap().get(&TomlKey::from(key)).unwrap()
+    }
+
+    #[test]
+    fn table_arrays() {
+        let toml = "[[bin]]\nname = \"a\"\n\n[[bin]]\nname = \"b\"\n";
+        let value = TomlValue::try_from(toml).unwrap();
+        let bins = get(&value, "bin").as_array().unwrap();
+
+        assert_eq!(bins.len(), 2);
+        assert_eq!(get(&bins[0], "name"), &TomlValue::String("a".into()));
+        assert_eq!(get(&bins[1], "name"), &TomlValue::String("b".into()));
+    }
+
+    #[test]
+    fn table_arrays_sub_tables() {
+        let toml = r#"
+[[fruits]]
+name = "apple"
+
+[fruits.physical]
+color = "red"
+
+[[fruits.varieties]]
+name = "red delicious"
+
+[[fruits.varieties]]
+name = "granny smith"
+
+[[fruits]]
+name = "banana"
+
+[[fruits.varieties]]
+name = "plantain"
+"#;
+        let value = TomlValue::try_from(toml).unwrap();
+        let fruits = get(&value, "fruits").as_array().unwrap();
+
+        assert_eq!(fruits.len(), 2);
+        assert_eq!(
+            get(get(&fruits[0], "physical"), "color"),
+            &TomlValue::String("red".into())
+        );
+        assert_eq!(get(&fruits[0], "varieties").as_array().unwrap().len(), 2);
+
+        let varieties = get(&fruits[1], "varieties").as_array().unwrap();
+        assert_eq!(varieties.len(), 1);
+        assert_eq!(
+            get(&varieties[0], "name"),
+            &TomlValue::String("plantain".into())
+        );
+    }
+
+    #[test]
+    fn negative_infinity() {
+        let value = TomlValue::try_from("[[a]]\nb = -inf\n").unwrap();
+        let a = get(&value, "a").as_array().unwrap();
+
+        assert_eq!(get(&a[0], "b"), &TomlValue::Float(f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn top_level_keys() {
+        let value = TomlValue::try_from("a = 1\n[[b]]\nc = 2\n").unwrap();
+
+        assert_eq!(get(&value, "a"), &TomlValue::Int(1));
+        assert!(value.as_table().unwrap().get(&TomlKey::None).is_none());
+    }
+
+    #[test]
+    fn key_order() {
+        let toml = "z = 1\n[b]\ny = 2\nx = { w = 3, v = 4 }\n[a]\n[b.c]\n[[d]]\n";
+        let value = TomlValue::try_from(toml).unwrap();
+        let keys = |value: &TomlValue| -> Vec<String> {
+            value
+                .as_table()
+                .unwrap()
+                .keys()
+                .map(ToString::to_string)
+                .collect()
+        };
+
+        assert_eq!(keys(&value), ["z", "b", "a", "d"]);
+        assert_eq!(keys(get(&value, "b")), ["y", "x", "c"]);
+        assert_eq!(keys(get(get(&value, "b"), "x")), ["w", "v"]);
+    }
+
+    #[test]
+    fn dotted_keys() {
+        let toml = r#"
+name.first = "Tom"
+physical . color = "orange"
+physical.shape = "round"
+
+[server.http]
+port = 80
+tls.enabled = true
+"#;
+        let value = TomlValue::try_from(toml).unwrap();
+
+        assert_eq!(
+            get(get(&value, "name"), "first"),
+            &TomlValue::String("Tom".into())
+        );
+        assert_eq!(
+            get(get(&value, "physical"), "color"),
+            &TomlValue::String("orange".into())
+        );
+        assert_eq!(
+            get(get(&value, "physical"), "shape"),
+            &TomlValue::String("round".into())
+        );
+
+        let http = get(get(&value, "server"), "http");
+        assert_eq!(get(http, "port"), &TomlValue::Int(80));
+        assert_eq!(get(get(http, "tls"), "enabled"), &TomlValue::Boolean(true));
+    }
+
+    #[test]
+    fn dotted_keys_inline_table() {
+        let value = TomlValue::try_from("point = { x.y = 1, x.z = 2 }").unwrap();
+        let x = get(get(&value, "point"), "x");
+
+        assert_eq!(get(x, "y"), &TomlValue::Int(1));
+        assert_eq!(get(x, "z"), &TomlValue::Int(2));
+    }
+
+    #[test]
+    fn quoted_keys() {
+        let toml = r#"
+"127.0.0.1" = "localhost"
+'quoted key' = 1
+"escaped \"key\"" = 2
+
+[dog."tater.man"]
+type.name = "pug"
+"#;
+        let value = TomlValue::try_from(toml).unwrap();
+
+        assert_eq!(
+            get(&value, "127.0.0.1"),
+            &TomlValue::String("localhost".into())
+        );
+        assert_eq!(get(&value, "quoted key"), &TomlValue::Int(1));
+        assert_eq!(get(&value, "escaped \"key\""), &TomlValue::Int(2));
+
+        let tater = get(get(&value, "dog"), "tater.man");
+        assert_eq!(
+            get(get(tater, "type"), "name"),
+            &TomlValue::String("pug".into())
+        );
+
+        let value = TomlValue::try_from(r#"table = { "a b" = 1, 'c' = 2 }"#).unwrap();
+        assert_eq!(get(get(&value, "table"), "a b"), &TomlValue::Int(1));
+        assert_eq!(get(get(&value, "table"), "c"), &TomlValue::Int(2));
+    }
+
+    #[test]
+    fn implicit_tables() {
+        // defining a super-table after its sub-table is fine
+        assert!(TomlValue::try_from("[x.y.z]\n[x]\n").is_ok());
+        // [fruit.apple.texture] only adds a sub-table
+        assert!(TomlValue::try_from("[fruit]\napple.color = 1\n[fruit.apple.texture]\n").is_ok());
+
+        // tables created by dotted keys cannot be redefined by headers
+        assert!(TomlValue::try_from("[fruit]\napple.color = 1\n[fruit.apple]\n").is_err());
+        assert!(TomlValue::try_from("a.b = 1\n[a]\n").is_err());
+        // nor can headers be extended by dotted keys
+        assert!(TomlValue::try_from("[a.b]\n[a]\nb.c = 1\n").is_err());
+        // a table cannot be defined twice
+        assert!(TomlValue::try_from("[a]\n[a]\n").is_err());
+        assert!(TomlValue::try_from("[a.b]\n[a]\n[a]\n").is_err());
+    }
+
+    #[test]
+    fn table_array_conflicts() {
+        assert!(TomlValue::try_from("[a]\n[[a]]\n").is_err());
+        assert!(TomlValue::try_from("a = 1\n[[a]]\n").is_err());
+        assert!(TomlValue::try_from("[[a]]\nb = 1\n[a.b]\n").is_err());
+    }
+
+    #[test]
+    fn duplicates() {
+        fn spans(source: &str) -> (Span, Span) {
+            let err = TomlValue::try_from(source).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::NameUsed);
+            (err.original().unwrap(), err.span().unwrap())
+        }
+
+        // keys
+        assert_eq!(spans("a = 1\na = 2\n"), (Span::new(0, 1), Span::new(6, 7)));
+        assert_eq!(
+            spans("[t]\n\"b\" = 1\n'b' = 2\n"),
+            (Span::new(4, 7), Span::new(12, 15))
+        );
+        // headers
+        assert_eq!(
+            spans("[ t.u ]\n[t.u]\n"),
+            (Span::new(2, 5), Span::new(9, 12))
+        );
+        // inline tables and static arrays are closed
+        assert_eq!(
+            spans("a = {}\n[a.b]\n"),
+            (Span::new(0, 1), Span::new(8, 11))
+        );
+        assert_eq!(
+            spans("a = {}\na.b = 1\n"),
+            (Span::new(0, 1), Span::new(7, 10))
+        );
+        assert_eq!(
+            spans("a = []\n[[a]]\n"),
+            (Span::new(0, 1), Span::new(9, 10))
+        );
+        assert_eq!(
+            spans("a.b = 1\na = {}\n"),
+            (Span::new(0, 3), Span::new(8, 9))
+        );
+        assert_eq!(
+            spans("t = { a = 1, a = 2 }"),
+            (Span::new(6, 7), Span::new(13, 14))
+        );
+
+        // every `[[a]]` starts a new table
+        assert!(TomlValue::try_from("[[a]]\nb = 1\n[[a]]\nb = 2\n").is_ok());
+    }
+
+    #[test]
+    fn strict_values() {
+        for toml in [
+            "a =",
+            "a = \n",
+            "a = \"b\" c",
+            "a = [1] 2",
+            "a = { b = 1 } c",
+            "a = 'b' # c\n d = 1 e",
+            "[a] b",
+            "a = [1, 2",
+            "a = \"b",
+            "a = '''b",
+        ] {
+            assert!(TomlValue::try_from(toml).is_err(), "{}", toml);
+        }
+
+        for toml in [
+            "a = \"b\" # c",
+            "a = [1, 2, ] \t",
+            "a = { b = 1 }\r\n",
+            "[a] # b",
+        ] {
+            assert!(TomlValue::try_from(toml).is_ok(), "{}", toml);
+        }
     }
 }
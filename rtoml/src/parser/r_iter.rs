@@ -1,43 +1,75 @@
 use crate::lexer::{Token, TokenSized};
 use crate::parser::r_slice::{RIndex, RSlice};
+use crate::ParseOptions;
 
 use std::iter::Iterator;
 use std::slice::Iter;
 
 #[derive(Clone, Debug)]
-pub struct RIter<'a> {
-    pub iter: Iter<'a, TokenSized<'a>>,
+// `'t` is the lifetime of the lexed tokens, `'a` the one of the input they
+// were lexed from. Parsed values only borrow from the input, so the tokens
+// can be dropped once parsing is done
+pub struct RIter<'t, 'a> {
+    pub iter: Iter<'t, TokenSized<'a>>,
     pub index: RIndex,
-    pub(crate) peeked: Option<&'a TokenSized<'a>>,
+    pub(crate) peeked: Option<&'t TokenSized<'a>>,
+    pub(crate) limits: Limits<'t>,
 }
 
-impl<'a> RIter<'a> {
-    pub fn new(slice: &'a [TokenSized<'a>]) -> Self {
+// the limits of a parse and how close the document got to them so far, they
+// move along with the tokens
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Limits<'t> {
+    pub options: &'t ParseOptions,
+    // arrays and inline tables the parser is in
+    pub depth: usize,
+    // keys and headers parsed
+    pub keys: usize,
+}
+
+impl<'t> Limits<'t> {
+    fn new(options: &'t ParseOptions) -> Self {
+        Self {
+            options,
+            depth: 0,
+            keys: 0,
+        }
+    }
+}
+
+impl<'t, 'a> RIter<'t, 'a> {
+    pub fn new(slice: &'t [TokenSized<'a>]) -> Self {
+        Self::with_options(slice, &ParseOptions::DEFAULT)
+    }
+
+    pub fn with_options(slice: &'t [TokenSized<'a>], options: &'t ParseOptions) -> Self {
         Self {
             iter: slice.iter(),
             index: RIndex::new(),
             peeked: None,
+            limits: Limits::new(options),
         }
     }
-    pub fn from(slice: RSlice<'a>) -> Self {
+
+    pub fn from(slice: RSlice<'t, 'a>) -> Self {
         Self {
             iter: slice.slice.iter(),
             index: slice.index,
             peeked: slice.peeked,
+            limits: slice.limits,
         }
     }
 
-    pub fn as_slice(&self) -> RSlice<'a> {
+    pub fn as_slice(&self) -> RSlice<'t, 'a> {
         RSlice::from(self)
     }
 
-    pub fn peek(&mut self) -> Option<&TokenSized> {
-        if self.peeked.is_some() {
-            self.peeked
-        } else {
-            self.peeked = self.next();
-            self.peeked
+    // the index only moves once the peeked token is taken by `next`
+    pub fn peek(&mut self) -> Option<&'t TokenSized<'a>> {
+        if self.peeked.is_none() {
+            self.peeked = self.iter.next();
         }
+        self.peeked
     }
 
     pub fn next_if_eq(&mut self, token: Token) -> bool {
@@ -54,24 +86,16 @@ impl<'a> RIter<'a> {
     }
 }
 
-impl<'a> Iterator for RIter<'a> {
-    type Item = &'a TokenSized<'a>;
+impl<'t, 'a> Iterator for RIter<'t, 'a> {
+    type Item = &'t TokenSized<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(x) = self.peeked {
-            self.peeked = None;
-            Some(x)
-        } else {
-            let x = self.iter.next();
-            match x {
-                Some((Token::LineBreak, _)) => {
-                    self.index.col = 0;
-                    self.index.line += 1;
-                }
-                Some((_, n)) => self.index.col += n,
-                _ => (),
-            }
-            x
+        let next = self.peeked.take().or_else(|| self.iter.next());
+
+        if let Some(token) = next {
+            self.index.advance(token);
         }
+
+        next
     }
 }
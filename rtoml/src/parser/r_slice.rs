@@ -1,39 +1,84 @@
-use crate::lexer::TokenSized;
-use crate::parser::r_iter::RIter;
+use crate::lexer::{Token, TokenSized};
+use crate::parser::r_iter::{Limits, RIter};
 
 use std::fmt::{Display, Formatter};
 
-#[derive(Clone, Copy, Default, Debug)]
+// position in the input, `line` and `col` start at 0 and `col` is in bytes,
+// see `LineIndex` for utf-16 columns
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
 pub struct RIndex {
     pub line: usize,
     pub col: usize,
+    // byte offset just past the last token read
+    pub offset: usize,
+    // byte offset the current line starts at
+    line_start: usize,
 }
 
 impl RIndex {
     pub fn new() -> Self {
-        Self { line: 0, col: 0 }
+        Self::default()
+    }
+
+    // move past `token`, positions are computed from the spans of the
+    // tokens so bytes skipped by the lexer, like comments, are counted too
+    pub(crate) fn advance(&mut self, (token, span): &TokenSized) {
+        if *token == Token::LineBreak {
+            self.line += 1;
+            self.line_start = span.end;
+        }
+
+        self.offset = span.end;
+        self.col = self.offset - self.line_start;
+    }
+
+    // position of `offset` in `source`, for errors found before or while lexing
+    pub(crate) fn locate(source: &[u8], offset: usize) -> Self {
+        let before = &source[..offset.min(source.len())];
+        let line_start = before
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |index| index + 1);
+
+        Self {
+            line: before.iter().filter(|byte| **byte == b'\n').count(),
+            col: before.len() - line_start,
+            offset: before.len(),
+            line_start,
+        }
+    }
+
+    // position of `offset`, which is on the line of `self` and after it
+    pub(crate) fn at(&self, offset: usize) -> Self {
+        Self {
+            col: offset - self.line_start,
+            offset,
+            ..*self
+        }
     }
 }
 
 #[derive(Clone, Copy, Debug)]
-pub struct RSlice<'a> {
-    pub slice: &'a [TokenSized<'a>],
+pub struct RSlice<'t, 'a> {
+    pub slice: &'t [TokenSized<'a>],
     pub index: RIndex,
-    pub peeked: Option<&'a TokenSized<'a>>,
+    pub peeked: Option<&'t TokenSized<'a>>,
+    pub(crate) limits: Limits<'t>,
 }
 
-impl<'a> From<&RIter<'a>> for RSlice<'a> {
-    fn from(iter: &RIter<'a>) -> Self {
+impl<'t, 'a> From<&RIter<'t, 'a>> for RSlice<'t, 'a> {
+    fn from(iter: &RIter<'t, 'a>) -> Self {
         Self {
             slice: iter.iter.as_slice(),
             index: iter.index,
             peeked: iter.peeked,
+            limits: iter.limits,
         }
     }
 }
 
 impl Display for RIndex {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "line: {}, column: {}", self.line, self.col)
+        write!(f, "line: {}, column: {}", self.line + 1, self.col + 1)
     }
 }
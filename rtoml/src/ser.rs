@@ -0,0 +1,708 @@
+use crate::emitter::Emitter;
+use crate::{DateTime, Table, TomlKey, TomlValue};
+
+use std::fmt;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::ser::{
+    self, Impossible, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
+    SerializeTuple, SerializeTupleStruct, SerializeTupleVariant,
+};
+use serde::{Serialize, Serializer};
+
+// newtype struct name date-times are serialized under, so they are written
+// as toml date-times rather than strings
+const DATETIME: &str = "$__rtoml_private_datetime";
+
+// serialize `value` as a toml document
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, Error> {
+    Ok(Emitter::new(false).emit(&to_table(value)?))
+}
+
+// serialize `value` as a toml document with arrays spread over multiple lines
+pub fn to_string_pretty<T: Serialize + ?Sized>(value: &T) -> Result<String, Error> {
+    Ok(Emitter::new(true).emit(&to_table(value)?))
+}
+
+fn to_table<T: Serialize + ?Sized>(value: &T) -> Result<Table<'static>, Error> {
+    match value.serialize(ValueSerializer)? {
+        Some(TomlValue::Table(table)) => Ok(table),
+        _ => Err(ser::Error::custom(
+            "only tables can be serialized as a toml document",
+        )),
+    }
+}
+
+// a value that has no toml representation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    message: String,
+}
+
+impl Error {
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error {
+            message: msg.to_string(),
+        }
+    }
+}
+
+impl Serialize for TomlValue<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            TomlValue::Int(x) => serializer.serialize_i64(*x),
+            TomlValue::Float(x) => serializer.serialize_f64(*x),
+            TomlValue::String(x) => serializer.serialize_str(x),
+            TomlValue::Boolean(x) => serializer.serialize_bool(*x),
+            TomlValue::DateTime(x) => x.serialize(serializer),
+            TomlValue::Array(x) => serializer.collect_seq(x),
+            TomlValue::Table(x) => serializer.collect_map(x),
+        }
+    }
+}
+
+impl Serialize for TomlKey<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl Serialize for DateTime {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(DATETIME, &self.to_string())
+    }
+}
+
+// serializes into a `TomlValue`, `None` is returned for missing values which
+// are left out of tables
+struct ValueSerializer;
+
+impl ValueSerializer {
+    fn value<T: Serialize + ?Sized>(value: &T) -> Result<TomlValue<'static>, Error> {
+        value
+            .serialize(ValueSerializer)?
+            .ok_or_else(|| ser::Error::custom("toml arrays can't contain `None`"))
+    }
+}
+
+impl Serializer for ValueSerializer {
+    type Ok = Option<TomlValue<'static>>;
+    type Error = Error;
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeVariant<SerializeArray>;
+    type SerializeMap = SerializeTable;
+    type SerializeStruct = SerializeTable;
+    type SerializeStructVariant = SerializeVariant<SerializeTable>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(TomlValue::Boolean(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(TomlValue::Int(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(v.into())
+    }
+
+    // toml integers are 64 bit signed integers
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        if v > i64::MAX as u64 {
+            Err(ser::Error::custom(format!(
+                "{} is out of range for toml",
+                v
+            )))
+        } else {
+            self.serialize_i64(v as i64)
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(TomlValue::Float(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(TomlValue::String(v.to_string())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        self.collect_seq(v)
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Err(ser::Error::custom("toml has no unit value"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        match (name, value.serialize(self)?) {
+            (DATETIME, Some(TomlValue::String(x))) => DateTime::from_str(&x)
+                .map(|datetime| Some(TomlValue::DateTime(datetime)))
+                .map_err(|err| ser::Error::custom(err.to_string())),
+            (_, value) => Ok(value),
+        }
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        let mut table = Table::default();
+        if let Some(value) = value.serialize(self)? {
+            table.insert(TomlKey::from(variant), value);
+        }
+        Ok(Some(TomlValue::Table(table)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(SerializeArray(Vec::with_capacity(len.unwrap_or(0))))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(SerializeVariant {
+            variant,
+            inner: SerializeArray(Vec::with_capacity(len)),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(SerializeTable::default())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(SerializeTable::default())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(SerializeVariant {
+            variant,
+            inner: SerializeTable::default(),
+        })
+    }
+}
+
+struct SerializeArray(Vec<TomlValue<'static>>);
+
+impl SerializeSeq for SerializeArray {
+    type Ok = Option<TomlValue<'static>>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.0.push(ValueSerializer::value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(TomlValue::Array(self.0)))
+    }
+}
+
+impl SerializeTuple for SerializeArray {
+    type Ok = Option<TomlValue<'static>>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        SerializeSeq::end(self)
+    }
+}
+
+impl SerializeTupleStruct for SerializeArray {
+    type Ok = Option<TomlValue<'static>>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        SerializeSeq::end(self)
+    }
+}
+
+#[derive(Default)]
+struct SerializeTable {
+    table: Table<'static>,
+    key: Option<TomlKey<'static>>,
+}
+
+impl SerializeTable {
+    fn insert<T: Serialize + ?Sized>(
+        &mut self,
+        key: TomlKey<'static>,
+        value: &T,
+    ) -> Result<(), Error> {
+        if let Some(value) = value.serialize(ValueSerializer)? {
+            self.table.insert(key, value);
+        }
+        Ok(())
+    }
+}
+
+impl SerializeMap for SerializeTable {
+    type Ok = Option<TomlValue<'static>>;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        self.key = Some(TomlKey::from(key.serialize(KeySerializer)?));
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        match self.key.take() {
+            Some(key) => self.insert(key, value),
+            None => Err(ser::Error::custom("map value serialized before its key")),
+        }
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(TomlValue::Table(self.table)))
+    }
+}
+
+impl SerializeStruct for SerializeTable {
+    type Ok = Option<TomlValue<'static>>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.insert(TomlKey::from(key), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        SerializeMap::end(self)
+    }
+}
+
+// enum variants with content are written as a table with the variant name
+// as its only key
+struct SerializeVariant<T> {
+    variant: &'static str,
+    inner: T,
+}
+
+impl<T> SerializeVariant<T> {
+    fn wrap(
+        variant: &'static str,
+        value: Option<TomlValue<'static>>,
+    ) -> Option<TomlValue<'static>> {
+        let mut table = Table::default();
+        if let Some(value) = value {
+            table.insert(TomlKey::from(variant), value);
+        }
+        Some(TomlValue::Table(table))
+    }
+}
+
+impl SerializeTupleVariant for SerializeVariant<SerializeArray> {
+    type Ok = Option<TomlValue<'static>>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        SerializeSeq::serialize_element(&mut self.inner, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Self::wrap(self.variant, SerializeSeq::end(self.inner)?))
+    }
+}
+
+impl SerializeStructVariant for SerializeVariant<SerializeTable> {
+    type Ok = Option<TomlValue<'static>>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.inner.insert(TomlKey::from(key), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Self::wrap(self.variant, SerializeMap::end(self.inner)?))
+    }
+}
+
+// table keys have to be strings
+struct KeySerializer;
+
+macro_rules! key_to_string {
+    ( $( $method : ident : $ty : ty ),* ) => {
+        $(
+            fn $method(self, v: $ty) -> Result<Self::Ok, Self::Error> {
+                Ok(v.to_string())
+            }
+        )*
+    };
+}
+
+macro_rules! key_unsupported {
+    ( $( $method : ident $( : $ty : ty )? ),* ) => {
+        $(
+            fn $method(self $( , _v: $ty )?) -> Result<Self::Ok, Self::Error> {
+                Err(ser::Error::custom("toml keys must be strings"))
+            }
+        )*
+    };
+}
+
+impl Serializer for KeySerializer {
+    type Ok = String;
+    type Error = Error;
+    type SerializeSeq = Impossible<String, Error>;
+    type SerializeTuple = Impossible<String, Error>;
+    type SerializeTupleStruct = Impossible<String, Error>;
+    type SerializeTupleVariant = Impossible<String, Error>;
+    type SerializeMap = Impossible<String, Error>;
+    type SerializeStruct = Impossible<String, Error>;
+    type SerializeStructVariant = Impossible<String, Error>;
+
+    key_to_string!(
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_char: char,
+        serialize_str: &str
+    );
+
+    key_unsupported!(
+        serialize_f32: f32,
+        serialize_f64: f64,
+        serialize_bytes: &[u8],
+        serialize_none,
+        serialize_unit
+    );
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Err(ser::Error::custom("toml keys must be strings"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(ser::Error::custom("toml keys must be strings"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(ser::Error::custom("toml keys must be strings"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(ser::Error::custom("toml keys must be strings"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(ser::Error::custom("toml keys must be strings"))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Err(ser::Error::custom("toml keys must be strings"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(ser::Error::custom("toml keys must be strings"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_str;
+
+    use std::collections::BTreeMap;
+    use std::convert::TryFrom;
+
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Manifest {
+        name: String,
+        version: Option<String>,
+        edition: Option<u16>,
+        features: BTreeMap<String, Vec<String>>,
+        dependencies: Dependencies,
+        bin: Vec<Bin>,
+        released: DateTime,
+        mode: Mode,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Dependencies {
+        serde: Dependency,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Dependency {
+        version: String,
+        optional: bool,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Bin {
+        name: String,
+        path: String,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    enum Mode {
+        Release,
+        Custom { level: u8 },
+    }
+
+    fn manifest() -> Manifest {
+        let mut features = BTreeMap::new();
+        features.insert(String::from("default"), vec![String::from("std")]);
+
+        Manifest {
+            name: String::from("rtoml"),
+            version: Some(String::from("0.1.1")),
+            edition: None,
+            features,
+            dependencies: Dependencies {
+                serde: Dependency {
+                    version: String::from("1.0"),
+                    optional: true,
+                },
+            },
+            bin: vec![
+                Bin {
+                    name: String::from("a"),
+                    path: String::from("src/a.rs"),
+                },
+                Bin {
+                    name: String::from("b \"quoted\""),
+                    path: String::from("src/b.rs"),
+                },
+            ],
+            released: DateTime::from_str("1979-05-27T07:32:00Z").unwrap(),
+            mode: Mode::Custom { level: 3 },
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let manifest = manifest();
+
+        for toml in [
+            to_string(&manifest).unwrap(),
+            to_string_pretty(&manifest).unwrap(),
+        ] {
+            assert_eq!(from_str::<Manifest>(&toml).unwrap(), manifest);
+
+            let value = TomlValue::try_from(toml.as_str()).unwrap();
+            let emitted = to_string(&value).unwrap();
+            assert_eq!(TomlValue::try_from(emitted.as_str()).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn section_order() {
+        let toml = to_string(&manifest()).unwrap();
+        let bin = toml.find("[[bin]]").unwrap();
+
+        assert!(toml.find("name = \"rtoml\"").unwrap() < bin);
+        assert!(toml.find("released = 1979-05-27T07:32:00Z").unwrap() < bin);
+        assert!(toml.find("[dependencies.serde]").unwrap() < bin);
+        assert!(!toml.contains("edition"));
+        assert!(toml.contains("default = [\"std\"]\n"));
+
+        let pretty = to_string_pretty(&manifest()).unwrap();
+        assert!(pretty.contains("default = [\n    \"std\",\n]\n"));
+    }
+
+    #[test]
+    fn unrepresentable_values() {
+        let document = "only tables can be serialized as a toml document";
+        assert_eq!(to_string(&1).unwrap_err().message(), document);
+        assert_eq!(to_string(&vec![1, 2]).unwrap_err().message(), document);
+        assert_eq!(to_string(&Mode::Release).unwrap_err().message(), document);
+
+        let mut map = BTreeMap::new();
+        map.insert("a", vec![Some(1), None]);
+        assert_eq!(
+            to_string(&map).unwrap_err().to_string(),
+            "toml arrays can't contain `None`"
+        );
+
+        let mut map = BTreeMap::new();
+        map.insert("a", u64::MAX);
+        assert_eq!(
+            to_string(&map).unwrap_err().to_string(),
+            "18446744073709551615 is out of range for toml"
+        );
+
+        let mut map = BTreeMap::new();
+        map.insert(vec![1], 1);
+        assert_eq!(
+            to_string(&map).unwrap_err().to_string(),
+            "toml keys must be strings"
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use serde_json::{Result, Value};
+use serde_json::Value;
 
 use std::fs;
 use std::os::unix::ffi::OsStrExt;
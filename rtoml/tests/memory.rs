@@ -0,0 +1,57 @@
+use rtoml::prelude::*;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+// keeps track of the bytes currently allocated by each thread, the harness
+// allocates on its own threads while the test runs
+struct Counting;
+
+thread_local! {
+    static ALLOCATED: Cell<isize> = const { Cell::new(0) };
+}
+
+fn allocated() -> isize {
+    ALLOCATED.with(Cell::get)
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATED.try_with(|x| x.set(x.get() + layout.size() as isize));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ = ALLOCATED.try_with(|x| x.set(x.get() - layout.size() as isize));
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const CONFIG: &str = r#"
+title = "config"
+
+[server]
+host = "127.0.0.1"
+ports = [8000, 8001, 8002]
+
+[[server.routes]]
+path = "/"
+"#;
+
+#[test]
+fn repeated_parsing_frees_tokens() {
+    // warm up anything lazily allocated on the first parse
+    drop(TomlValue::try_from(CONFIG).unwrap());
+
+    let before = allocated();
+
+    for _ in 0..1000 {
+        let value = TomlValue::try_from(CONFIG).unwrap();
+        assert!(value.as_table().is_some());
+    }
+
+    assert_eq!(before, allocated());
+}